@@ -0,0 +1,162 @@
+// Pasting into other windows: XTest on X11. Wayland has no protocol for
+// that every compositor offers, so the wtype (virtual-keyboard) and ydotool
+// (uinput) helpers are used there.
+
+use crate::config::PasteKey;
+use std::io;
+use std::process::Command;
+
+fn is_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+fn err(e: impl std::fmt::Display) -> String {
+    e.to_string()
+}
+
+/// The focused window on X11, None on Wayland where the compositor gives
+/// the focus back by itself
+pub fn active_window() -> Option<u32> {
+    if is_wayland() {
+        return None;
+    }
+    x11::active_window().unwrap_or_else(|e| {
+        eprintln!("Cannot tell the focused window: {}", e);
+        None
+    })
+}
+
+/// Focus `window` again if given, then press the paste keys
+pub fn send_paste(key: PasteKey, window: Option<u32>) -> Result<(), String> {
+    if is_wayland() {
+        wayland::send(key)
+    } else {
+        x11::send(key, window)
+    }
+}
+
+mod x11 {
+    use super::err;
+    use crate::config::PasteKey;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{
+        AtomEnum, ClientMessageEvent, ConnectionExt as _, EventMask, Keycode, Window, KEY_PRESS_EVENT,
+        KEY_RELEASE_EVENT,
+    };
+    use x11rb::protocol::xtest::ConnectionExt as _;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::wrapper::ConnectionExt as _;
+    use x11rb::CURRENT_TIME;
+
+    const XK_CONTROL_L: u32 = 0xffe3;
+    const XK_SHIFT_L: u32 = 0xffe1;
+    const XK_INSERT: u32 = 0xff63;
+    const XK_V: u32 = 0x0076;
+
+    pub fn active_window() -> Result<Option<u32>, String> {
+        crate::x11::with_connection(|conn, root| {
+            let net_active_window = conn.intern_atom(false, b"_NET_ACTIVE_WINDOW").map_err(err)?.reply().map_err(err)?.atom;
+            let reply = conn.get_property(false, root, net_active_window, AtomEnum::WINDOW, 0, 1)
+                .map_err(err)?
+                .reply()
+                .map_err(err)?;
+            Ok(reply.value32().and_then(|mut values| values.next()).filter(|&window| window != 0))
+        })
+    }
+
+    pub fn send(key: PasteKey, window: Option<Window>) -> Result<(), String> {
+        crate::x11::with_connection(|conn, root| press(conn, root, key, window))
+    }
+
+    fn press(conn: &RustConnection, root: Window, key: PasteKey, window: Option<Window>) -> Result<(), String> {
+        conn.xtest_get_version(2, 2).map_err(err)?.reply().map_err(|_| "XTest isn't available".to_string())?;
+
+        if let Some(window) = window {
+            // Ask the window manager, as a pager would (source indication 2)
+            let net_active_window = conn.intern_atom(false, b"_NET_ACTIVE_WINDOW").map_err(err)?.reply().map_err(err)?.atom;
+            let event = ClientMessageEvent::new(32, window, net_active_window, [2, CURRENT_TIME, 0, 0, 0]);
+            conn.send_event(false, root, EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY, event)
+                .map_err(err)?;
+            conn.sync().map_err(err)?;
+        }
+
+        let keysyms: &[u32] = match key {
+            PasteKey::CtrlV => &[XK_CONTROL_L, XK_V],
+            PasteKey::ShiftInsert => &[XK_SHIFT_L, XK_INSERT],
+            PasteKey::CtrlShiftV => &[XK_CONTROL_L, XK_SHIFT_L, XK_V],
+        };
+        let keycodes = keysyms.iter()
+            .map(|&keysym| keycode(conn, keysym).ok_or(format!("no key for keysym {:#x}", keysym)))
+            .collect::<Result<Vec<Keycode>, String>>()?;
+
+        // Press in order, release in reverse
+        for &keycode in &keycodes {
+            conn.xtest_fake_input(KEY_PRESS_EVENT, keycode, CURRENT_TIME, root, 0, 0, 0).map_err(err)?;
+        }
+        for &keycode in keycodes.iter().rev() {
+            conn.xtest_fake_input(KEY_RELEASE_EVENT, keycode, CURRENT_TIME, root, 0, 0, 0).map_err(err)?;
+        }
+        conn.sync().map_err(err)
+    }
+
+    /// First keycode producing `keysym` without modifiers
+    fn keycode(conn: &impl Connection, keysym: u32) -> Option<Keycode> {
+        let setup = conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let mapping = conn.get_keyboard_mapping(min, max - min + 1).ok()?.reply().ok()?;
+        let per_keycode = mapping.keysyms_per_keycode as usize;
+        if per_keycode == 0 {
+            return None;
+        }
+        mapping.keysyms.chunks(per_keycode)
+            .position(|syms| syms.first() == Some(&keysym))
+            .map(|index| min + index as u8)
+    }
+}
+
+mod wayland {
+    use super::{err, run};
+    use crate::config::PasteKey;
+    use std::io;
+
+    // Linux input event codes, for ydotool
+    const KEY_LEFTCTRL: u32 = 29;
+    const KEY_LEFTSHIFT: u32 = 42;
+    const KEY_V: u32 = 47;
+    const KEY_INSERT: u32 = 110;
+
+    pub fn send(key: PasteKey) -> Result<(), String> {
+        let wtype_args: &[&str] = match key {
+            PasteKey::CtrlV => &["-M", "ctrl", "v", "-m", "ctrl"],
+            PasteKey::ShiftInsert => &["-M", "shift", "-k", "Insert", "-m", "shift"],
+            PasteKey::CtrlShiftV => &["-M", "ctrl", "-M", "shift", "v", "-m", "shift", "-m", "ctrl"],
+        };
+        // Not installed, or the compositor lacks virtual-keyboard support: try uinput
+        if run("wtype", wtype_args).unwrap_or(false) {
+            return Ok(());
+        }
+
+        let codes: &[u32] = match key {
+            PasteKey::CtrlV => &[KEY_LEFTCTRL, KEY_V],
+            PasteKey::ShiftInsert => &[KEY_LEFTSHIFT, KEY_INSERT],
+            PasteKey::CtrlShiftV => &[KEY_LEFTCTRL, KEY_LEFTSHIFT, KEY_V],
+        };
+        let mut ydotool_args = vec!["key".to_string()];
+        ydotool_args.extend(codes.iter().map(|code| format!("{}:1", code)));
+        ydotool_args.extend(codes.iter().rev().map(|code| format!("{}:0", code)));
+        let ydotool_args: Vec<&str> = ydotool_args.iter().map(String::as_str).collect();
+        match run("ydotool", &ydotool_args) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("ydotool failed, is ydotoold running?".to_string()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Err("automatic paste on Wayland needs wtype or ydotool".to_string())
+            }
+            Err(e) => Err(err(e)),
+        }
+    }
+}
+
+/// Run a helper, Ok(false) if it ran but failed
+fn run(program: &str, args: &[&str]) -> io::Result<bool> {
+    Ok(Command::new(program).args(args).status()?.success())
+}
@@ -0,0 +1,286 @@
+use crate::color::Color;
+use crate::models::ClipboardContent;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+static UUID: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?i)[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$").expect("valid uuid pattern")
+});
+
+static URL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?i)(?:[a-z][a-z0-9+.\-]*://\S+|www\.[^\s/]+\.[^\s]+)$").expect("valid url pattern")
+});
+
+static EMAIL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?i)(?:mailto:)?[a-z0-9._%+\-]+@[a-z0-9.\-]+\.[a-z]{2,}$").expect("valid email pattern")
+});
+
+// Plain, grouped, decimal, scientific or hex
+static NUMBER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?i)(?:[+\-]?(?:\d{1,3}(?:,\d{3})+|\d+)(?:\.\d+)?(?:e[+\-]?\d+)?|0x[0-9a-f]+)$")
+        .expect("valid number pattern")
+});
+
+static PHONE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\+?[\d\s().\-]{7,20}$").expect("valid phone pattern"));
+
+// A `key:` or `- item` line
+static YAML_LINE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^\s*(?:-\s+\S|-$|[\w"'.\-]+\s*:(?:\s|$))"#).expect("valid yaml pattern")
+});
+
+static CODE_LINE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(concat!(
+        r"(?:[;{}]\s*$|^\s*(?:fn|pub|let|const|def|class|function|import|from|return|if|for|while|",
+        r"#include|#define|use|package|func|var|public|private|static|SELECT|INSERT|UPDATE)\b|=>|->|==|\+=)",
+    )).expect("valid code pattern")
+});
+
+// Single line shell commands, prompts included
+static SHELL_COMMAND: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:\$\s+|sudo\s+|#!)|\s(?:\||&&|\|\|)\s|\s--?[a-zA-Z][\w\-]*(?:=\S+)?(?:\s|$)")
+        .expect("valid shell pattern")
+});
+
+/// What a text item looks like, detected when it is captured
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TextKind {
+    #[default]
+    Plain,
+    Url,
+    Email,
+    Color,
+    Path,
+    Json,
+    Yaml,
+    Code,
+    Number,
+    Phone,
+    Uuid,
+}
+
+impl TextKind {
+    pub const ALL: [TextKind; 11] = [
+        TextKind::Plain,
+        TextKind::Url,
+        TextKind::Email,
+        TextKind::Color,
+        TextKind::Path,
+        TextKind::Json,
+        TextKind::Yaml,
+        TextKind::Code,
+        TextKind::Number,
+        TextKind::Phone,
+        TextKind::Uuid,
+    ];
+
+    /// Kind of `content`, only plain text items are looked at
+    pub fn of(content: &ClipboardContent) -> Self {
+        match content {
+            ClipboardContent::Text(text) => classify(text),
+            _ => TextKind::Plain,
+        }
+    }
+
+    /// Name used by `is:` searches and listings
+    pub fn name(self) -> &'static str {
+        match self {
+            TextKind::Plain => "plain",
+            TextKind::Url => "url",
+            TextKind::Email => "email",
+            TextKind::Color => "color",
+            TextKind::Path => "path",
+            TextKind::Json => "json",
+            TextKind::Yaml => "yaml",
+            TextKind::Code => "code",
+            TextKind::Number => "number",
+            TextKind::Phone => "phone",
+            TextKind::Uuid => "uuid",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "colour" => Some(TextKind::Color),
+            "mail" => Some(TextKind::Email),
+            "link" => Some(TextKind::Url),
+            name => Self::ALL.into_iter().find(|kind| kind.name() == name),
+        }
+    }
+}
+
+pub fn classify(text: &str) -> TextKind {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return TextKind::Plain;
+    }
+
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(trimmed).is_ok()
+    {
+        return TextKind::Json;
+    }
+
+    if !trimmed.contains('\n') {
+        return classify_line(trimmed);
+    }
+
+    let lines: Vec<&str> = trimmed.lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .collect();
+    if looks_like_yaml(trimmed, &lines) {
+        return TextKind::Yaml;
+    }
+    // Code needs a few lines with code in them, prose has the odd semicolon
+    let code_lines = lines.iter().filter(|line| CODE_LINE.is_match(line)).count();
+    if code_lines >= 2 && code_lines * 3 >= lines.len() {
+        return TextKind::Code;
+    }
+    TextKind::Plain
+}
+
+fn classify_line(line: &str) -> TextKind {
+    if UUID.is_match(line) {
+        TextKind::Uuid
+    } else if Color::parse(line).is_some() {
+        TextKind::Color
+    } else if EMAIL.is_match(line) {
+        TextKind::Email
+    } else if URL.is_match(line) {
+        TextKind::Url
+    } else if NUMBER.is_match(line) {
+        TextKind::Number
+    } else if is_phone(line) {
+        TextKind::Phone
+    } else if is_path(line) {
+        TextKind::Path
+    } else if SHELL_COMMAND.is_match(line) || CODE_LINE.is_match(line) {
+        TextKind::Code
+    } else {
+        TextKind::Plain
+    }
+}
+
+/// Phone numbers have 7 to 15 digits and are written with a country code
+/// or separators, which tells them apart from plain numbers
+fn is_phone(line: &str) -> bool {
+    if !PHONE.is_match(line) {
+        return false;
+    }
+    // Dates and IPv4 addresses are made of the same characters
+    let groups: Vec<&str> = line.split(['-', '.']).collect();
+    let date = groups.len() == 3 && groups[0].len() == 4;
+    let address = line.contains('.') && groups.len() == 4 && groups.iter().all(|g| (1..=3).contains(&g.len()));
+    if date || address {
+        return false;
+    }
+    let digits = line.chars().filter(|c| c.is_ascii_digit()).count();
+    (7..=15).contains(&digits) && (line.starts_with('+') || line.contains([' ', '-', '(', '.']))
+}
+
+fn is_path(line: &str) -> bool {
+    let unix = ["/", "~/", "./", "../"].iter().any(|prefix| line.starts_with(prefix));
+    let windows = line.len() > 2
+        && line.as_bytes()[0].is_ascii_alphabetic()
+        && (line[1..].starts_with(":\\") || line[1..].starts_with(":/"));
+    // Sentences starting with a slash command aren't paths
+    (unix || windows) && !line.contains("  ") && line.split_whitespace().count() <= 3
+}
+
+fn looks_like_yaml(text: &str, lines: &[&str]) -> bool {
+    if text.starts_with("---\n") {
+        return true;
+    }
+    let keys = lines.iter().filter(|line| YAML_LINE.is_match(line)).count();
+    let has_mapping = lines.iter().any(|line| !line.trim_start().starts_with('-') && YAML_LINE.is_match(line));
+    // Code also has `key:` lines (labels, dict literals), but ends lines with punctuation
+    let code_endings = lines.iter().any(|line| line.trim_end().ends_with([';', '{', '}', ',']));
+    has_mapping && !code_endings && keys * 5 >= lines.len() * 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_line_kinds() {
+        let cases = [
+            ("https://example.com/a?b=c", TextKind::Url),
+            ("www.example.com/page", TextKind::Url),
+            ("someone@example.org", TextKind::Email),
+            ("mailto:someone@example.org", TextKind::Email),
+            ("#ff8800", TextKind::Color),
+            ("rgb(1, 2, 3)", TextKind::Color),
+            ("550e8400-e29b-41d4-a716-446655440000", TextKind::Uuid),
+            ("42", TextKind::Number),
+            ("-1,234.5", TextKind::Number),
+            ("6.02e23", TextKind::Number),
+            ("0xDEADBEEF", TextKind::Number),
+            ("+49 30 1234567", TextKind::Phone),
+            ("(555) 123-4567", TextKind::Phone),
+            ("/usr/share/applications", TextKind::Path),
+            ("~/Downloads/file.txt", TextKind::Path),
+            ("C:\\Users\\me", TextKind::Path),
+            ("$ ls -la", TextKind::Code),
+            ("cargo build --release", TextKind::Code),
+            ("let x = 1;", TextKind::Code),
+            ("just a sentence", TextKind::Plain),
+        ];
+        for (text, kind) in cases {
+            assert_eq!(classify(text), kind, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn dates_and_addresses_are_not_phone_numbers() {
+        assert_ne!(classify("2024-01-15"), TextKind::Phone);
+        assert_ne!(classify("192.168.100.1"), TextKind::Phone);
+        // Too few digits
+        assert_ne!(classify("12-34"), TextKind::Phone);
+    }
+
+    #[test]
+    fn sentences_with_slashes_are_not_paths() {
+        assert_eq!(classify("/me waves at everyone in the channel"), TextKind::Plain);
+    }
+
+    #[test]
+    fn json_needs_to_parse() {
+        assert_eq!(classify("{\"a\": [1, 2]}"), TextKind::Json);
+        assert_eq!(classify("[1, 2, 3]"), TextKind::Json);
+        assert_ne!(classify("{not json"), TextKind::Json);
+    }
+
+    #[test]
+    fn yaml_documents() {
+        assert_eq!(classify("name: app\nversion: 1\nitems:\n  - a\n  - b"), TextKind::Yaml);
+        assert_eq!(classify("---\nanything"), TextKind::Yaml);
+    }
+
+    #[test]
+    fn code_blocks() {
+        let rust = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}";
+        assert_eq!(classify(rust), TextKind::Code);
+        // A single semicolon doesn't make prose code
+        let prose = "Dear team;\nthe meeting moved to Friday.\nSee you there.\nBest regards";
+        assert_eq!(classify(prose), TextKind::Plain);
+    }
+
+    #[test]
+    fn only_text_items_are_classified() {
+        let html = ClipboardContent::Html { html: "<a>https://example.com</a>".into(), text: "https://example.com".into() };
+        assert_eq!(TextKind::of(&html), TextKind::Plain);
+        assert_eq!(TextKind::of(&ClipboardContent::Text("  ".into())), TextKind::Plain);
+        assert_eq!(TextKind::of(&ClipboardContent::Text(" https://example.com \n".into())), TextKind::Url);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for kind in TextKind::ALL {
+            assert_eq!(TextKind::from_name(kind.name()), Some(kind));
+        }
+        assert_eq!(TextKind::from_name("colour"), Some(TextKind::Color));
+        assert_eq!(TextKind::from_name("link"), Some(TextKind::Url));
+        assert_eq!(TextKind::from_name("nope"), None);
+    }
+}
@@ -0,0 +1,367 @@
+use crate::clipboard::AddOutcome;
+use crate::daemon::DaemonClient;
+use crate::models::{ClipboardContent, ClipboardItem, Selection};
+use crate::transform::Transform;
+use base64::Engine;
+use chrono::{Local, SecondsFormat};
+use gtk4::gio;
+use gtk4::glib::translate::ToGlibPtr;
+use gtk4::prelude::*;
+use serde_json::{json, Value};
+use std::ffi::CString;
+
+const USAGE: &str = "\
+Usage: clipboard_manager [--json] <command> [args]
+       clipboard_manager --daemon
+
+Without a command the popup window is toggled. --daemon records the
+clipboard without a window, the popup and commands then connect to it.
+Otherwise recording runs inside the popup's process.
+
+Commands:
+  list                  List the history (index, id, pinned, time, preview)
+  get <id|index>        Print the full content of an item (--reveal to print
+                        sensitive items too)
+  copy <id|index>       Put an item back on the clipboard (--primary for the
+                        middle-click selection, --as=<transform> to convert
+                        its text first: plain, trim, upper, lower, title,
+                        snake, camel, json-pretty, json-minify, url-encode,
+                        url-decode, base64-encode, base64-decode,
+                        shell-escape, regex-escape)
+  pin <id|index>        Pin an item
+  unpin <id|index>      Unpin an item
+  delete <id|index>     Remove an item from the history
+  clear [--all]         Remove all unpinned items (--all also removes pinned)
+  add                   Add text read from stdin to the history
+                        (CLIPBOARD_STATE=sensitive keeps it in memory only)
+
+Options:
+  --json                Print machine readable JSON instead of plain text
+  -h, --help            Show this help
+";
+
+#[derive(Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Plain,
+    Json,
+}
+
+enum Command {
+    List,
+    Get { target: String, reveal: bool },
+    Copy { target: String, selection: Selection, transform: Option<Transform> },
+    Pin(String),
+    Unpin(String),
+    Delete(String),
+    Clear { all: bool },
+    Add,
+    Help,
+}
+
+/// Handle a command line forwarded to the primary instance, returns the exit status
+pub fn handle_command_line(
+    app: &impl IsA<gio::Application>,
+    cmdline: &gio::ApplicationCommandLine,
+    client: &DaemonClient,
+) -> i32 {
+    let args: Vec<String> = cmdline
+        .arguments()
+        .into_iter()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+
+    if args.is_empty() {
+        app.activate();
+        return 0;
+    }
+
+    let (command, format) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            printerr(cmdline, &format!("{}\n\n{}", e, USAGE));
+            return 2;
+        }
+    };
+
+    match run_command(command, format, cmdline, client) {
+        Ok(output) => {
+            if !output.is_empty() {
+                print(cmdline, &output);
+            }
+            0
+        }
+        Err(e) => {
+            printerr(cmdline, &format!("error: {}\n", e));
+            1
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<(Command, OutputFormat), String> {
+    let mut format = OutputFormat::Plain;
+    let mut all = false;
+    let mut primary = false;
+    let mut transform = None;
+    let mut reveal = false;
+    let mut positional = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "--json" => format = OutputFormat::Json,
+            "--all" => all = true,
+            "--primary" => primary = true,
+            "--reveal" => reveal = true,
+            "-h" | "--help" => return Ok((Command::Help, format)),
+            s if s.starts_with("--as=") => {
+                let name = &s["--as=".len()..];
+                transform = Some(Transform::from_name(name).ok_or(format!("unknown transform '{}'", name))?);
+            }
+            s if s.starts_with('-') && s.len() > 1 => {
+                return Err(format!("unknown option '{}'", s));
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let name = positional.next().ok_or("missing command")?;
+    let mut target = || positional.next().ok_or(format!("'{}' needs an <id|index> argument", name));
+
+    let command = match name.as_str() {
+        "list" => Command::List,
+        "get" => Command::Get { target: target()?, reveal },
+        "copy" => Command::Copy {
+            target: target()?,
+            selection: if primary { Selection::Primary } else { Selection::Clipboard },
+            transform,
+        },
+        "pin" => Command::Pin(target()?),
+        "unpin" => Command::Unpin(target()?),
+        "delete" => Command::Delete(target()?),
+        "clear" => Command::Clear { all },
+        "add" => Command::Add,
+        "help" => Command::Help,
+        other => return Err(format!("unknown command '{}'", other)),
+    };
+
+    Ok((command, format))
+}
+
+fn run_command(
+    command: Command,
+    format: OutputFormat,
+    cmdline: &gio::ApplicationCommandLine,
+    client: &DaemonClient,
+) -> Result<String, String> {
+    match command {
+        Command::Help => Ok(USAGE.to_string()),
+        Command::List => {
+            let items = client.items()?;
+            match format {
+                OutputFormat::Json => {
+                    let list: Vec<Value> = items.iter()
+                        .enumerate()
+                        .map(|(index, item)| item_json(index, item, false))
+                        .collect();
+                    Ok(format!("{}\n", Value::Array(list)))
+                }
+                OutputFormat::Plain => Ok(items.iter()
+                    .enumerate()
+                    .map(|(index, item)| {
+                        format!(
+                            "{}\t{}\t{}\t{}\t{}\n",
+                            index,
+                            item.id,
+                            if item.pinned { "pinned" } else { "-" },
+                            item.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                            item.preview(),
+                        )
+                    })
+                    .collect()),
+            }
+        }
+        Command::Get { target, reveal } => {
+            let items = client.items()?;
+            let (index, item) = lookup(&items, &target)?;
+            // Secrets only leave through the popup unless asked for explicitly
+            let redacted = item.is_sensitive() && !reveal;
+            let mut item = item.clone();
+            if item.truncated && !redacted {
+                item.content = client.content(&item.id)?.ok_or("item content not found")?;
+            }
+            match format {
+                OutputFormat::Json => {
+                    // The preview is masked, and "sensitive" says why
+                    let mut value = item_json(index, &item, !redacted);
+                    if let Some(png_data) = client.image_png(&item.id)? {
+                        value["size"] = json!(png_data.len());
+                        value["png_base64"] = json!(base64::engine::general_purpose::STANDARD.encode(png_data));
+                    }
+                    Ok(format!("{}\n", value))
+                }
+                OutputFormat::Plain if redacted => {
+                    Err(format!("item '{}' is sensitive, pass --reveal to print it", item.id))
+                }
+                OutputFormat::Plain => match &item.content {
+                    // Binary can't go through the command line channel, emit base64 PNG
+                    ClipboardContent::Image { .. } => {
+                        let png_data = client.image_png(&item.id)?.ok_or("image data not found")?;
+                        Ok(format!("{}\n", base64::engine::general_purpose::STANDARD.encode(png_data)))
+                    }
+                    content => Ok(content.plain_text().unwrap_or_default()),
+                },
+            }
+        }
+        Command::Copy { target, selection, transform } => {
+            let id = lookup(&client.items()?, &target)?.1.id.clone();
+            match transform {
+                Some(transform) => client.paste_transformed(&id, selection, transform)?,
+                None => client.paste_item(&id, selection)?,
+            }
+            Ok(status(format, "copied", &id))
+        }
+        Command::Pin(target) => set_pinned(client, &target, true, format),
+        Command::Unpin(target) => set_pinned(client, &target, false, format),
+        Command::Delete(target) => {
+            let id = lookup(&client.items()?, &target)?.1.id.clone();
+            client.delete_item(&id)?;
+            Ok(status(format, "deleted", &id))
+        }
+        Command::Clear { all } => {
+            let removed = client.clear(all)?;
+            match format {
+                OutputFormat::Json => Ok(format!("{}\n", json!({ "removed": removed }))),
+                OutputFormat::Plain => Ok(format!("removed {} items\n", removed)),
+            }
+        }
+        Command::Add => {
+            let text = read_stdin(cmdline)?;
+            if text.is_empty() {
+                return Err("nothing to add, stdin was empty".to_string());
+            }
+
+            // wl-paste --watch sets this for copies flagged by a password manager
+            let sensitive = cmdline.getenv("CLIPBOARD_STATE").as_deref() == Some("sensitive");
+            match client.add_text(text, sensitive)? {
+                AddOutcome::Added(id) => Ok(status(format, "added", &id)),
+                AddOutcome::Unchanged(id) => Ok(status(format, "unchanged", &id)),
+                AddOutcome::Ignored => match format {
+                    OutputFormat::Json => Ok(format!("{}\n", json!({ "status": "ignored", "id": null }))),
+                    OutputFormat::Plain => Ok("ignored\n".to_string()),
+                },
+            }
+        }
+    }
+}
+
+fn set_pinned(
+    client: &DaemonClient,
+    target: &str,
+    pinned: bool,
+    format: OutputFormat,
+) -> Result<String, String> {
+    let id = lookup(&client.items()?, target)?.1.id.clone();
+    client.set_pinned(&id, pinned)?;
+    Ok(status(format, if pinned { "pinned" } else { "unpinned" }, &id))
+}
+
+fn lookup<'a>(items: &'a [ClipboardItem], target: &str) -> Result<(usize, &'a ClipboardItem), String> {
+    if let Ok(index) = target.parse::<usize>() {
+        return items.get(index)
+            .map(|item| (index, item))
+            .ok_or_else(|| format!("no item at index {}", index));
+    }
+    items.iter()
+        .enumerate()
+        .find(|(_, item)| item.id == target)
+        .ok_or_else(|| format!("no item with id '{}'", target))
+}
+
+fn item_json(index: usize, item: &ClipboardItem, full: bool) -> Value {
+    let mut value = json!({
+        "index": index,
+        "id": item.id,
+        "pinned": item.pinned,
+        "timestamp": item.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+        "source": match item.source {
+            Selection::Clipboard => "clipboard",
+            Selection::Primary => "primary",
+        },
+    });
+
+    value["kind"] = json!(item.content.kind_name());
+    value["sensitive"] = json!(item.is_sensitive());
+    if let Some(source_app) = &item.source_app {
+        value["app"] = json!(source_app.id);
+        if let Some(title) = &source_app.title {
+            value["app_title"] = json!(title);
+        }
+    }
+    match &item.content {
+        ClipboardContent::Image { width, height, .. } => {
+            value["width"] = json!(width);
+            value["height"] = json!(height);
+        }
+        content => {
+            value["text"] = if full { json!(content.plain_text()) } else { json!(item.preview()) };
+            if full {
+                match content {
+                    ClipboardContent::Html { html, .. } => value["html"] = json!(html),
+                    ClipboardContent::Rtf { rtf, .. } => value["rtf"] = json!(rtf),
+                    ClipboardContent::Files(uris) => value["uris"] = json!(uris),
+                    _ => {}
+                }
+            }
+            if matches!(content, ClipboardContent::Text(_)) && !item.is_sensitive() {
+                value["text_kind"] = json!(item.kind.name());
+            }
+        }
+    }
+    value
+}
+
+fn status(format: OutputFormat, action: &str, id: &str) -> String {
+    match format {
+        OutputFormat::Json => format!("{}\n", json!({ "status": action, "id": id })),
+        OutputFormat::Plain => format!("{} {}\n", action, id),
+    }
+}
+
+fn read_stdin(cmdline: &gio::ApplicationCommandLine) -> Result<String, String> {
+    let stream = cmdline.stdin().ok_or("stdin is not available")?;
+    let mut data = Vec::new();
+    loop {
+        let chunk = stream
+            .read_bytes(64 * 1024, gio::Cancellable::NONE)
+            .map_err(|e| e.to_string())?;
+        if chunk.is_empty() {
+            break;
+        }
+        data.extend_from_slice(&chunk);
+    }
+    String::from_utf8(data).map_err(|_| "stdin is not valid UTF-8".to_string())
+}
+
+// gio-rs doesn't bind the varargs print functions, go through "%s" ourselves
+fn print(cmdline: &gio::ApplicationCommandLine, message: &str) {
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    unsafe {
+        gio::ffi::g_application_command_line_print(
+            cmdline.to_glib_none().0,
+            c"%s".as_ptr(),
+            message.as_ptr(),
+        );
+    }
+}
+
+fn printerr(cmdline: &gio::ApplicationCommandLine, message: &str) {
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    unsafe {
+        gio::ffi::g_application_command_line_printerr(
+            cmdline.to_glib_none().0,
+            c"%s".as_ptr(),
+            message.as_ptr(),
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use serde::{Deserialize, Serialize};
+
+/// Color parsed from a copied CSS color (`#ff8800`, `rgb(…)`, `hsl(…)`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+/// Notations a color can be copied as
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorFormat {
+    Hex,
+    Rgb,
+    Hsl,
+    // @define-color line for GTK style sheets
+    Gtk,
+    // CSS custom property
+    CssVariable,
+}
+
+impl ColorFormat {
+    pub const ALL: [ColorFormat; 5] = [
+        ColorFormat::Hex,
+        ColorFormat::Rgb,
+        ColorFormat::Hsl,
+        ColorFormat::Gtk,
+        ColorFormat::CssVariable,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorFormat::Hex => "hex",
+            ColorFormat::Rgb => "rgb",
+            ColorFormat::Hsl => "hsl",
+            ColorFormat::Gtk => "gtk",
+            ColorFormat::CssVariable => "css",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorFormat::Hex => "Copy as Hex",
+            ColorFormat::Rgb => "Copy as RGB",
+            ColorFormat::Hsl => "Copy as HSL",
+            ColorFormat::Gtk => "Copy as GTK Color",
+            ColorFormat::CssVariable => "Copy as CSS Variable",
+        }
+    }
+}
+
+impl Color {
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().to_ascii_lowercase();
+        if let Some(hex) = text.strip_prefix('#') {
+            return parse_hex(hex);
+        }
+
+        let open = text.find('(')?;
+        let args = text[open + 1..].strip_suffix(')')?;
+        // Both `rgb(1, 2, 3, 0.5)` and `rgb(1 2 3 / 50%)`
+        let parts: Vec<&str> = args
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .collect();
+        if !(3..=4).contains(&parts.len()) {
+            return None;
+        }
+        let a = match parts.get(3) {
+            Some(alpha) => channel(parse_alpha(alpha)?),
+            None => 255,
+        };
+
+        match &text[..open] {
+            "rgb" | "rgba" => Some(Color {
+                r: parse_rgb_channel(parts[0])?,
+                g: parse_rgb_channel(parts[1])?,
+                b: parse_rgb_channel(parts[2])?,
+                a,
+            }),
+            "hsl" | "hsla" => {
+                let hue = parts[0].strip_suffix("deg").unwrap_or(parts[0]).parse::<f64>().ok()?;
+                let saturation = parse_percent(parts[1])?;
+                let lightness = parse_percent(parts[2])?;
+                let (r, g, b) = hsl_to_rgb(hue, saturation, lightness);
+                Some(Color { r: channel(r), g: channel(g), b: channel(b), a })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn format(&self, format: ColorFormat) -> String {
+        let hex = self.hex();
+        match format {
+            ColorFormat::Hex => hex,
+            ColorFormat::Rgb if self.a == 255 => format!("rgb({}, {}, {})", self.r, self.g, self.b),
+            ColorFormat::Rgb => format!("rgba({}, {}, {}, {})", self.r, self.g, self.b, self.alpha_text()),
+            ColorFormat::Hsl => {
+                let (h, s, l) = self.hsl();
+                if self.a == 255 {
+                    format!("hsl({}, {}%, {}%)", h, s, l)
+                } else {
+                    format!("hsla({}, {}%, {}%, {})", h, s, l, self.alpha_text())
+                }
+            }
+            ColorFormat::Gtk if self.a == 255 => format!("@define-color copied_color {};", hex),
+            ColorFormat::Gtk => format!(
+                "@define-color copied_color alpha(#{:02x}{:02x}{:02x}, {});",
+                self.r, self.g, self.b, self.alpha_text(),
+            ),
+            ColorFormat::CssVariable => format!("--copied-color: {};", hex),
+        }
+    }
+
+    /// Components from 0 to 1, for drawing
+    pub fn to_rgba_f64(self) -> (f64, f64, f64, f64) {
+        let unit = |c: u8| c as f64 / 255.0;
+        (unit(self.r), unit(self.g), unit(self.b), unit(self.a))
+    }
+
+    fn hex(&self) -> String {
+        if self.a == 255 {
+            format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+        }
+    }
+
+    fn alpha_text(&self) -> String {
+        let alpha = format!("{:.2}", self.a as f64 / 255.0);
+        alpha.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+
+    /// Hue in degrees, saturation and lightness in percent, rounded
+    fn hsl(&self) -> (u32, u32, u32) {
+        let (r, g, b, _) = self.to_rgba_f64();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let lightness = (max + min) / 2.0;
+        let delta = max - min;
+        if delta == 0.0 {
+            return (0, 0, (lightness * 100.0).round() as u32);
+        }
+
+        let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+        let hue = if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        (
+            hue.round() as u32 % 360,
+            (saturation * 100.0).round() as u32,
+            (lightness * 100.0).round() as u32,
+        )
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|d| d * 17);
+    let pair = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    match hex.len() {
+        3 => Some(Color { r: digit(0)?, g: digit(1)?, b: digit(2)?, a: 255 }),
+        4 => Some(Color { r: digit(0)?, g: digit(1)?, b: digit(2)?, a: digit(3)? }),
+        6 => Some(Color { r: pair(0)?, g: pair(2)?, b: pair(4)?, a: 255 }),
+        8 => Some(Color { r: pair(0)?, g: pair(2)?, b: pair(4)?, a: pair(6)? }),
+        _ => None,
+    }
+}
+
+fn parse_percent(part: &str) -> Option<f64> {
+    let value = part.strip_suffix('%')?.parse::<f64>().ok()?;
+    (0.0..=100.0).contains(&value).then_some(value / 100.0)
+}
+
+/// 0-255 or a percentage
+fn parse_rgb_channel(part: &str) -> Option<u8> {
+    if part.ends_with('%') {
+        return Some(channel(parse_percent(part)?));
+    }
+    let value = part.parse::<f64>().ok()?;
+    (0.0..=255.0).contains(&value).then(|| value.round() as u8)
+}
+
+/// 0-1 or a percentage
+fn parse_alpha(part: &str) -> Option<f64> {
+    if part.ends_with('%') {
+        return parse_percent(part);
+    }
+    let value = part.parse::<f64>().ok()?;
+    (0.0..=1.0).contains(&value).then_some(value)
+}
+
+fn channel(unit: f64) -> u8 {
+    (unit * 255.0).round() as u8
+}
+
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> (f64, f64, f64) {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    (r + m, g + m, b + m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!(Color::parse("#ff8800"), Some(rgba(255, 136, 0, 255)));
+        assert_eq!(Color::parse(" #F80 "), Some(rgba(255, 136, 0, 255)));
+        assert_eq!(Color::parse("#f808"), Some(rgba(255, 136, 0, 136)));
+        assert_eq!(Color::parse("#ff880080"), Some(rgba(255, 136, 0, 128)));
+        assert_eq!(Color::parse("#ff88"), Some(rgba(255, 255, 136, 136)));
+        assert_eq!(Color::parse("#ff880"), None);
+        assert_eq!(Color::parse("#gg8800"), None);
+    }
+
+    #[test]
+    fn parses_rgb() {
+        assert_eq!(Color::parse("rgb(255, 136, 0)"), Some(rgba(255, 136, 0, 255)));
+        assert_eq!(Color::parse("rgba(255, 136, 0, 0.5)"), Some(rgba(255, 136, 0, 128)));
+        assert_eq!(Color::parse("rgb(255 136 0 / 50%)"), Some(rgba(255, 136, 0, 128)));
+        assert_eq!(Color::parse("RGB(100%, 0%, 0%)"), Some(rgba(255, 0, 0, 255)));
+        assert_eq!(Color::parse("rgb(256, 0, 0)"), None);
+        assert_eq!(Color::parse("rgb(1, 2)"), None);
+        assert_eq!(Color::parse("rgba(1, 2, 3, 2)"), None);
+        assert_eq!(Color::parse("rgb(1, 2, 3"), None);
+    }
+
+    #[test]
+    fn parses_hsl() {
+        assert_eq!(Color::parse("hsl(0, 100%, 50%)"), Some(rgba(255, 0, 0, 255)));
+        assert_eq!(Color::parse("hsl(120deg 100% 25%)"), Some(rgba(0, 128, 0, 255)));
+        assert_eq!(Color::parse("hsla(240, 100%, 50%, 0.2)"), Some(rgba(0, 0, 255, 51)));
+        // Hue wraps around
+        assert_eq!(Color::parse("hsl(360, 100%, 50%)"), Color::parse("hsl(0, 100%, 50%)"));
+        assert_eq!(Color::parse("hsl(0, 100, 50%)"), None);
+        assert_eq!(Color::parse("cmyk(0, 0, 0)"), None);
+    }
+
+    #[test]
+    fn formats() {
+        let orange = rgba(255, 136, 0, 255);
+        assert_eq!(orange.format(ColorFormat::Hex), "#ff8800");
+        assert_eq!(orange.format(ColorFormat::Rgb), "rgb(255, 136, 0)");
+        assert_eq!(orange.format(ColorFormat::Hsl), "hsl(32, 100%, 50%)");
+        assert_eq!(orange.format(ColorFormat::Gtk), "@define-color copied_color #ff8800;");
+        assert_eq!(orange.format(ColorFormat::CssVariable), "--copied-color: #ff8800;");
+
+        let translucent = rgba(255, 136, 0, 128);
+        assert_eq!(translucent.format(ColorFormat::Hex), "#ff880080");
+        assert_eq!(translucent.format(ColorFormat::Rgb), "rgba(255, 136, 0, 0.5)");
+        assert_eq!(translucent.format(ColorFormat::Hsl), "hsla(32, 100%, 50%, 0.5)");
+        assert_eq!(translucent.format(ColorFormat::Gtk), "@define-color copied_color alpha(#ff8800, 0.5);");
+        assert_eq!(rgba(0, 0, 0, 0).format(ColorFormat::Rgb), "rgba(0, 0, 0, 0)");
+        assert_eq!(rgba(0, 0, 0, 255).format(ColorFormat::Hsl), "hsl(0, 0%, 0%)");
+    }
+
+    #[test]
+    fn hex_and_rgb_round_trip() {
+        let colors = [rgba(0, 0, 0, 255), rgba(18, 52, 86, 255), rgba(255, 136, 0, 128), rgba(1, 2, 3, 0)];
+        for color in colors {
+            for format in [ColorFormat::Hex, ColorFormat::Rgb] {
+                assert_eq!(Color::parse(&color.format(format)), Some(color), "{}", color.format(format));
+            }
+        }
+    }
+
+    #[test]
+    fn hsl_round_trips_within_rounding() {
+        for color in [rgba(255, 0, 0, 255), rgba(0, 128, 0, 255), rgba(18, 52, 86, 255), rgba(200, 200, 200, 255)] {
+            let parsed = Color::parse(&color.format(ColorFormat::Hsl)).unwrap();
+            for (a, b) in [(parsed.r, color.r), (parsed.g, color.g), (parsed.b, color.b)] {
+                assert!(a.abs_diff(b) <= 2, "{:?} came back as {:?}", color, parsed);
+            }
+        }
+    }
+
+    #[test]
+    fn format_names_round_trip() {
+        for format in ColorFormat::ALL {
+            assert_eq!(ColorFormat::from_name(format.name()), Some(format));
+        }
+        assert_eq!(ColorFormat::from_name("cmyk"), None);
+    }
+}
@@ -0,0 +1,310 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    // Unpinned items kept per history
+    pub max_items: usize,
+    // Stricter limits for one kind of item within max_items, 0 for none
+    pub max_text_items: usize,
+    pub max_image_items: usize,
+    // Unpinned items older than this are dropped, 0 keeps them
+    pub max_age_days: u64,
+    // All unpinned items together, the largest images go first. 0 for no limit.
+    pub max_total_size_mb: usize,
+    // How often the limits are checked when nothing is being copied
+    pub prune_interval_minutes: u64,
+    pub max_text_size_mb: usize,
+    pub max_image_size_mb: usize,
+    // Longest side of the thumbnails shown in the list
+    pub thumbnail_size: u32,
+    // Remember the application and window title each copy came from (X11 only)
+    pub record_source_app: bool,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            max_items: 50,
+            max_text_items: 0,
+            max_image_items: 0,
+            max_age_days: 0,
+            max_total_size_mb: 0,
+            prune_interval_minutes: 10,
+            max_text_size_mb: 4,
+            max_image_size_mb: 50,
+            thumbnail_size: 80,
+            record_source_app: true,
+        }
+    }
+}
+
+impl HistoryConfig {
+    #[inline]
+    pub fn max_text_size(&self) -> usize {
+        self.max_text_size_mb * 1024 * 1024
+    }
+
+    #[inline]
+    pub fn max_image_size(&self) -> usize {
+        self.max_image_size_mb * 1024 * 1024
+    }
+
+    pub fn max_age(&self) -> Option<chrono::Duration> {
+        (self.max_age_days > 0).then(|| chrono::Duration::days(self.max_age_days as i64))
+    }
+
+    pub fn max_total_size(&self) -> Option<usize> {
+        (self.max_total_size_mb > 0).then(|| self.max_total_size_mb * 1024 * 1024)
+    }
+
+    #[inline]
+    pub fn prune_interval(&self) -> Duration {
+        Duration::from_secs(self.prune_interval_minutes.max(1) * 60)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: i32,
+    pub height: i32,
+    // Full text of the selected item next to the list, Ctrl+Space toggles it until the next start
+    pub preview: bool,
+    // Added to the width while the preview is shown
+    pub preview_width: i32,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 450,
+            height: 600,
+            preview: false,
+            preview_width: 450,
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn total_width(&self) -> i32 {
+        if self.preview { self.width + self.preview_width } else { self.width }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatcherConfig {
+    // Only used when neither data-control nor XFixes notifications are available
+    pub poll_interval_ms: u64,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self { poll_interval_ms: 250 }
+    }
+}
+
+impl WatcherConfig {
+    #[inline]
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms.max(10))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrimaryMode {
+    // Primary entries only show up when searching with is:primary
+    Separate,
+    // Primary entries are mixed into the main history
+    Merged,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrimaryConfig {
+    pub capture: bool,
+    pub mode: PrimaryMode,
+}
+
+impl Default for PrimaryConfig {
+    fn default() -> Self {
+        Self {
+            capture: false,
+            mode: PrimaryMode::Separate,
+        }
+    }
+}
+
+/// Keys sent to the focused window to paste
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PasteKey {
+    CtrlV,
+    ShiftInsert,
+    // Terminals take Ctrl+V as a control character
+    CtrlShiftV,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasteConfig {
+    // Paste into the window focused before the popup after picking an item
+    pub auto_paste: bool,
+    pub key: PasteKey,
+    // Time for the focus to get back to that window
+    pub delay_ms: u64,
+}
+
+impl Default for PasteConfig {
+    fn default() -> Self {
+        Self {
+            auto_paste: false,
+            key: PasteKey::CtrlV,
+            delay_ms: 150,
+        }
+    }
+}
+
+impl PasteConfig {
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SensitiveAction {
+    // Don't record sensitive content at all
+    Skip,
+    // Keep it in memory (never on disk) until it expires
+    Memory,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensitiveConfig {
+    pub action: SensitiveAction,
+    pub expire_seconds: u64,
+    // x-kde-passwordManagerHint from KeePassXC, KDE Wallet and friends
+    pub honor_hints: bool,
+    pub detect_api_keys: bool,
+    pub detect_credit_cards: bool,
+    pub detect_private_keys: bool,
+    // Extra regular expressions, matched against the plain text
+    pub patterns: Vec<String>,
+}
+
+impl Default for SensitiveConfig {
+    fn default() -> Self {
+        Self {
+            action: SensitiveAction::Memory,
+            expire_seconds: 60,
+            honor_hints: true,
+            detect_api_keys: true,
+            detect_credit_cards: true,
+            detect_private_keys: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeySource {
+    // Random key stored in the Secret Service keyring
+    Keyring,
+    // Derived from $CLIPBOARD_MANAGER_PASSPHRASE
+    Passphrase,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    pub enabled: bool,
+    pub key_source: KeySource,
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key_source: KeySource::Keyring,
+        }
+    }
+}
+
+/// User settings from `$XDG_CONFIG_HOME/clipboard_manager/config.toml`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub history: HistoryConfig,
+    pub window: WindowConfig,
+    pub watcher: WatcherConfig,
+    pub primary: PrimaryConfig,
+    pub paste: PasteConfig,
+    pub sensitive: SensitiveConfig,
+    pub encryption: EncryptionConfig,
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+        path.push("clipboard_manager");
+        path.push("config.toml");
+        path
+    }
+
+    /// Load the config file, falling back to defaults if it's missing or invalid.
+    /// Only for startup, reloads use `try_load` to keep what they have.
+    pub fn load() -> Self {
+        Self::try_load().unwrap_or_else(|e| {
+            eprintln!("Ignoring invalid config: {}", e);
+            Self::default()
+        })
+    }
+
+    /// Load the config file, defaults if it doesn't exist
+    pub fn try_load() -> Result<Self, String> {
+        let path = Self::path();
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let contents = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        write_atomic(&path, contents.as_bytes()).map_err(|e| e.to_string())
+    }
+}
+
+/// Replace `path` so that a crash leaves either the old or the new file, and
+/// the watcher never reloads half a config
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("toml.tmp");
+    let mut file = File::create(&tmp)?;
+    if let Err(e) = file.write_all(data).and_then(|_| file.sync_all()) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    drop(file);
+    fs::rename(&tmp, path)?;
+
+    // Make the rename itself durable
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
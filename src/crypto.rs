@@ -0,0 +1,226 @@
+// Authenticated encryption for the history file. The key is either random
+// and kept in the Secret Service keyring, or derived from a passphrase.
+
+use crate::config::{EncryptionConfig, KeySource};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use base64::Engine;
+
+// Sealed files: MAGIC, key source byte, salt, nonce, ciphertext
+const MAGIC: &[u8; 8] = b"CMGRENC1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+
+const KEYRING_SERVICE: &str = "clipboard_manager";
+const KEYRING_USER: &str = "history-key";
+pub const PASSPHRASE_ENV: &str = "CLIPBOARD_MANAGER_PASSPHRASE";
+
+pub struct Cipher {
+    source: KeySource,
+    key: Key,
+    // Passphrase keys are bound to the salt they were derived with
+    salt: [u8; SALT_LEN],
+    passphrase: Option<String>,
+}
+
+impl Cipher {
+    /// Cipher for the configured key source, None when encryption is disabled
+    pub fn from_config(config: &EncryptionConfig) -> Result<Option<Self>, String> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        Self::for_source(config.key_source).map(Some)
+    }
+
+    fn for_source(source: KeySource) -> Result<Self, String> {
+        match source {
+            KeySource::Keyring => Ok(Self {
+                source,
+                key: keyring_key()?,
+                salt: [0; SALT_LEN],
+                passphrase: None,
+            }),
+            KeySource::Passphrase => {
+                let passphrase = std::env::var(PASSPHRASE_ENV)
+                    .map_err(|_| format!("{} is not set", PASSPHRASE_ENV))?;
+                let mut salt = [0; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                Ok(Self {
+                    source,
+                    key: derive_key(&passphrase, &salt)?,
+                    salt,
+                    passphrase: Some(passphrase),
+                })
+            }
+        }
+    }
+
+    /// Cipher able to open an existing sealed file, used when encryption was turned off since
+    pub fn for_sealed(sealed: &[u8]) -> Result<Self, String> {
+        match sealed.get(MAGIC.len()) {
+            Some(0) => Self::for_source(KeySource::Keyring),
+            Some(1) => Self::for_source(KeySource::Passphrase),
+            _ => Err("unknown key source in history file".to_string()),
+        }
+    }
+
+    /// Whether `data` was written by `seal` (as opposed to a plain bincode history)
+    pub fn is_sealed(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&self.key)
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| "encryption failed".to_string())?;
+
+        let mut sealed = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        sealed.extend_from_slice(MAGIC);
+        sealed.push(source_tag(self.source));
+        sealed.extend_from_slice(&self.salt);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub fn open(&mut self, sealed: &[u8]) -> Result<Vec<u8>, String> {
+        if sealed.len() < HEADER_LEN || !Self::is_sealed(sealed) {
+            return Err("not an encrypted history file".to_string());
+        }
+        let (header, ciphertext) = sealed.split_at(HEADER_LEN);
+        let source = header[MAGIC.len()];
+        let salt = &header[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LEN];
+        let nonce = XNonce::from_slice(&header[MAGIC.len() + 1 + SALT_LEN..]);
+
+        if source != source_tag(self.source) {
+            return Err("history was encrypted with a different key source".to_string());
+        }
+
+        // Keep using the file's salt so the next save doesn't need another derivation
+        if let Some(passphrase) = &self.passphrase {
+            if salt != self.salt {
+                self.key = derive_key(passphrase, salt)?;
+                self.salt.copy_from_slice(salt);
+            }
+        }
+
+        XChaCha20Poly1305::new(&self.key)
+            .decrypt(nonce, ciphertext)
+            .map_err(|_| "wrong key or corrupted history file".to_string())
+    }
+}
+
+fn source_tag(source: KeySource) -> u8 {
+    match source {
+        KeySource::Keyring => 0,
+        KeySource::Passphrase => 1,
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, String> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+/// The stored key, generating and storing one on first use
+fn keyring_key() -> Result<Key, String> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(|e| e.to_string())?;
+    let engine = base64::engine::general_purpose::STANDARD;
+
+    match entry.get_password() {
+        Ok(encoded) => {
+            let bytes = engine.decode(encoded.trim()).map_err(|e| e.to_string())?;
+            if bytes.len() != 32 {
+                return Err("keyring entry does not hold a 256-bit key".to_string());
+            }
+            Ok(*Key::from_slice(&bytes))
+        }
+        Err(keyring::Error::NoEntry) => {
+            let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+            entry.set_password(&engine.encode(key)).map_err(|e| e.to_string())?;
+            Ok(key)
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyring_cipher() -> Cipher {
+        Cipher {
+            source: KeySource::Keyring,
+            key: XChaCha20Poly1305::generate_key(&mut OsRng),
+            salt: [0; SALT_LEN],
+            passphrase: None,
+        }
+    }
+
+    fn passphrase_cipher(passphrase: &str) -> Cipher {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Cipher {
+            source: KeySource::Passphrase,
+            key: derive_key(passphrase, &salt).unwrap(),
+            salt,
+            passphrase: Some(passphrase.to_string()),
+        }
+    }
+
+    #[test]
+    fn seal_open_round_trip() {
+        let mut cipher = keyring_cipher();
+        let sealed = cipher.seal(b"history").unwrap();
+        assert!(Cipher::is_sealed(&sealed));
+        assert!(!Cipher::is_sealed(b"history"));
+        assert_eq!(cipher.open(&sealed).unwrap(), b"history");
+    }
+
+    #[test]
+    fn nonces_are_not_reused() {
+        let cipher = keyring_cipher();
+        assert_ne!(cipher.seal(b"same").unwrap(), cipher.seal(b"same").unwrap());
+    }
+
+    #[test]
+    fn tampering_is_rejected() {
+        let mut cipher = keyring_cipher();
+        let sealed = cipher.seal(b"history").unwrap();
+
+        let mut flipped_ciphertext = sealed.clone();
+        *flipped_ciphertext.last_mut().unwrap() ^= 1;
+        assert!(cipher.open(&flipped_ciphertext).is_err());
+
+        let mut flipped_nonce = sealed.clone();
+        flipped_nonce[HEADER_LEN - 1] ^= 1;
+        assert!(cipher.open(&flipped_nonce).is_err());
+
+        assert!(cipher.open(&sealed[..HEADER_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn wrong_key_or_source_is_rejected() {
+        let sealed = keyring_cipher().seal(b"history").unwrap();
+        assert!(keyring_cipher().open(&sealed).is_err());
+
+        let mut passphrase = passphrase_cipher("secret");
+        assert!(passphrase.open(&sealed).is_err());
+    }
+
+    #[test]
+    fn passphrase_keys_follow_the_file_salt() {
+        let sealed = passphrase_cipher("secret").seal(b"history").unwrap();
+        // A new run picks another salt, the file's one has to be used
+        let mut reopened = passphrase_cipher("secret");
+        assert_eq!(reopened.open(&sealed).unwrap(), b"history");
+        assert!(passphrase_cipher("wrong").open(&sealed).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use crate::clipboard::SharedClipboardManager;
+use crate::models::Selection;
+use crate::watcher;
+use crossbeam_channel::{unbounded, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Primary changes on every drag step, wait until it has been stable this long
+const PRIMARY_SETTLE: Duration = Duration::from_millis(300);
+
+// Longest wait for primary to settle. The polling fallback reports it on
+// every tick, so it never does.
+const MAX_COALESCE: Duration = Duration::from_secs(1);
+
+// The clipboard connection is refreshed after this long without changes
+const IDLE_REFRESH: Duration = Duration::from_secs(60);
+
+/// Record clipboard changes into the history. Listeners learn about them
+/// through `ClipboardManager::subscribe`.
+pub fn spawn(manager: Arc<SharedClipboardManager>) {
+    // Only wakes up when the selection owner changes
+    let (change_sender, change_receiver) = unbounded::<Selection>();
+    let manager_poll = Arc::clone(&manager);
+    watcher::spawn(change_sender, move || manager_poll.0.read().config().watcher.poll_interval());
+
+    thread::spawn(move || {
+        let mut last_retention = Instant::now();
+        loop {
+            // Age limits apply even when nothing new is copied
+            let prune_interval = manager.0.read().config().history.prune_interval();
+            if last_retention.elapsed() >= prune_interval {
+                manager.0.write().apply_retention();
+                last_retention = Instant::now();
+            }
+
+            // Wake up in time to drop expired sensitive items
+            let timeout = manager.0.read().next_expiry()
+                .map_or(IDLE_REFRESH, |expiry| expiry.min(IDLE_REFRESH))
+                .min(prune_interval.saturating_sub(last_retention.elapsed()));
+            let first = match change_receiver.recv_timeout(timeout) {
+                Ok(selection) => selection,
+                Err(RecvTimeoutError::Timeout) => {
+                    let mut mgr = manager.0.write();
+                    if !mgr.prune_expired() {
+                        // Refresh the clipboard connection while idle
+                        mgr.refresh_clipboard();
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            // Clipboard changes are checked right away, primary changes are
+            // coalesced into one check once it has settled
+            let mut primary_deadline = None;
+            let mut next = Some(first);
+            while let Some(selection) = next {
+                // Changes already queued count once
+                let queued: Vec<Selection> = change_receiver.try_iter().collect();
+                let changed = |wanted| selection == wanted || queued.contains(&wanted);
+                if changed(Selection::Clipboard) {
+                    manager.0.write().check_clipboard_fast();
+                }
+                if changed(Selection::Primary) {
+                    primary_deadline.get_or_insert(Instant::now() + MAX_COALESCE);
+                }
+                next = primary_deadline
+                    .and_then(|deadline| deadline.checked_duration_since(Instant::now()))
+                    .and_then(|remaining| change_receiver.recv_timeout(PRIMARY_SETTLE.min(remaining)).ok());
+            }
+            if primary_deadline.is_some() {
+                manager.0.write().check_primary();
+            }
+        }
+    });
+}
@@ -0,0 +1,172 @@
+use super::protocol::{read_message, write_message, Request, Response, WireItem};
+use super::socket_path;
+use crate::clipboard::{AddOutcome, ManagerEvent};
+use crate::models::{ClipboardContent, ClipboardItem, Selection};
+use crate::transform::Transform;
+use crossbeam_channel::{unbounded, Receiver};
+use parking_lot::Mutex;
+use std::os::unix::net::UnixStream;
+use std::thread;
+
+/// Connection to the daemon, with methods mirroring ClipboardManager
+pub struct DaemonClient {
+    // None after the daemon went away, reconnected on the next request
+    stream: Mutex<Option<UnixStream>>,
+}
+
+impl DaemonClient {
+    pub fn connect() -> Result<Self, String> {
+        Ok(Self { stream: Mutex::new(Some(connect_socket()?)) })
+    }
+
+    pub fn items(&self) -> Result<Vec<ClipboardItem>, String> {
+        match self.request(&Request::Items)? {
+            Response::Items(items) => Ok(items.into_iter().map(WireItem::into_item).collect()),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub fn paste_item(&self, id: &str, selection: Selection) -> Result<(), String> {
+        self.done(&Request::Paste { id: id.to_string(), selection })
+    }
+
+    /// Paste the text of an item with `transform` applied, the item stays unchanged
+    pub fn paste_transformed(&self, id: &str, selection: Selection, transform: Transform) -> Result<(), String> {
+        self.done(&Request::PasteTransformed { id: id.to_string(), selection, transform })
+    }
+
+    /// Pin or unpin an item, returns false if the item doesn't exist
+    pub fn set_pinned(&self, id: &str, pinned: bool) -> Result<bool, String> {
+        self.found(&Request::SetPinned { id: id.to_string(), pinned })
+    }
+
+    pub fn toggle_pin(&self, id: &str) -> Result<(), String> {
+        self.done(&Request::TogglePin(id.to_string()))
+    }
+
+    /// Returns false if the item doesn't exist
+    pub fn delete_item(&self, id: &str) -> Result<bool, String> {
+        self.found(&Request::Delete(id.to_string()))
+    }
+
+    pub fn clear(&self, include_pinned: bool) -> Result<usize, String> {
+        match self.request(&Request::Clear { include_pinned })? {
+            Response::Removed(removed) => Ok(removed),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// Add text to the clipboard history, the outcome names the new or matching item
+    pub fn add_text(&self, text: String, sensitive: bool) -> Result<AddOutcome, String> {
+        match self.request(&Request::Add { text, sensitive })? {
+            Response::Added(outcome) => Ok(outcome),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// Save edited text over a text item or as a new item, returns the id of the saved item
+    pub fn edit_text(&self, id: &str, text: String, as_new: bool) -> Result<String, String> {
+        match self.request(&Request::EditText { id: id.to_string(), text, as_new })? {
+            Response::Edited(id) => Ok(id),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// Full size PNG of an image item
+    pub fn image_png(&self, id: &str) -> Result<Option<Vec<u8>>, String> {
+        match self.request(&Request::ImagePng(id.to_string()))? {
+            Response::Image(png_data) => Ok(png_data),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// Full content of an item, for items holding only the start of their text
+    pub fn content(&self, id: &str) -> Result<Option<ClipboardContent>, String> {
+        match self.request(&Request::Content(id.to_string()))? {
+            Response::Content(content) => Ok(content),
+            _ => Err(unexpected()),
+        }
+    }
+
+    pub fn take_storage_notice(&self) -> Result<Option<String>, String> {
+        match self.request(&Request::StorageNotice)? {
+            Response::Notice(notice) => Ok(notice),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// Receive a ManagerEvent for every change to the history, on a connection of its own.
+    /// The channel closes when the daemon goes away.
+    pub fn subscribe(&self) -> Result<Receiver<ManagerEvent>, String> {
+        let mut stream = connect_socket()?;
+        write_message(&mut stream, &Request::Subscribe).map_err(|e| e.to_string())?;
+        match read_message(&mut stream).map_err(|e| e.to_string())? {
+            Response::Done => {}
+            _ => return Err(unexpected()),
+        }
+
+        let (sender, receiver) = unbounded();
+        thread::spawn(move || {
+            while let Ok(Response::Event(event)) = read_message(&mut stream) {
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(receiver)
+    }
+
+    fn done(&self, request: &Request) -> Result<(), String> {
+        match self.request(request)? {
+            Response::Done => Ok(()),
+            _ => Err(unexpected()),
+        }
+    }
+
+    fn found(&self, request: &Request) -> Result<bool, String> {
+        match self.request(request)? {
+            Response::Found(found) => Ok(found),
+            _ => Err(unexpected()),
+        }
+    }
+
+    fn request(&self, request: &Request) -> Result<Response, String> {
+        let mut guard = self.stream.lock();
+
+        // Only retried when the request couldn't be sent, so nothing runs twice
+        let mut sent = false;
+        for _ in 0..2 {
+            if guard.is_none() {
+                *guard = Some(connect_socket()?);
+            }
+            let stream = guard.as_mut().unwrap();
+            if write_message(stream, request).is_ok() {
+                sent = true;
+                break;
+            }
+            *guard = None;
+        }
+        if !sent {
+            return Err("cannot reach the clipboard daemon".to_string());
+        }
+
+        let stream = guard.as_mut().unwrap();
+        match read_message(stream) {
+            Ok(Response::Error(e)) => Err(e),
+            Ok(response) => Ok(response),
+            Err(e) => {
+                *guard = None;
+                Err(format!("clipboard daemon went away: {}", e))
+            }
+        }
+    }
+}
+
+fn connect_socket() -> Result<UnixStream, String> {
+    let path = socket_path()?;
+    UnixStream::connect(&path).map_err(|e| format!("cannot connect to {}: {}", path.display(), e))
+}
+
+fn unexpected() -> String {
+    "unexpected reply from the clipboard daemon".to_string()
+}
@@ -0,0 +1,161 @@
+// Clipboard capture and storage, independent of any window. Runs headless
+// with --daemon, or inside the popup's process when no daemon is running.
+// Either way the popup and the command line talk to it over a Unix socket.
+
+mod capture;
+mod client;
+mod protocol;
+mod server;
+
+pub use client::DaemonClient;
+
+use crate::clipboard::SharedClipboardManager;
+use crate::config::Config;
+use crate::dbus;
+use gtk4::gio;
+use gtk4::glib;
+use gtk4::prelude::*;
+use std::fs;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const SOCKET_FILE: &str = "clipboard_manager.sock";
+
+// The application id is taken by the popup, which handles its command line
+const BUS_NAME: &str = "com.example.ClipboardManager.Daemon";
+const OBJECT_PATH: &str = "/com/example/ClipboardManager";
+
+pub struct Daemon {
+    pub manager: Arc<SharedClipboardManager>,
+    // Reloads the config while alive
+    _config_monitor: Option<gio::FileMonitor>,
+}
+
+/// Socket in `$XDG_RUNTIME_DIR`, or else in a directory of our own under the
+/// temp dir. Anyone who can reach it can read the whole history.
+pub fn socket_path() -> Result<PathBuf, String> {
+    let dir = match dirs::runtime_dir() {
+        Some(dir) => dir,
+        None => {
+            let dir = std::env::temp_dir().join(format!("clipboard_manager-{}", current_uid()));
+            match fs::DirBuilder::new().mode(0o700).create(&dir) {
+                // Checked below, it may have been made by someone else
+                Err(e) if e.kind() != io::ErrorKind::AlreadyExists => {
+                    return Err(format!("cannot create {}: {}", dir.display(), e));
+                }
+                _ => {}
+            }
+            dir
+        }
+    };
+    check_private(&dir)?;
+    Ok(dir.join(SOCKET_FILE))
+}
+
+/// Fails unless `dir` is a real directory owned by us that nobody else can enter
+fn check_private(dir: &Path) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(dir).map_err(|e| format!("cannot use {}: {}", dir.display(), e))?;
+    if !metadata.is_dir() || metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        return Err(format!("{} must be a directory only this user can access", dir.display()));
+    }
+    Ok(())
+}
+
+fn current_uid() -> u32 {
+    // Can't fail
+    unsafe { libc::getuid() }
+}
+
+/// Start capturing and serving the socket. Must be called on the thread running the main loop.
+pub fn start() -> Result<Daemon, String> {
+    let manager = SharedClipboardManager::new();
+    server::spawn(&socket_path()?, Arc::clone(&manager))?;
+    capture::spawn(Arc::clone(&manager));
+    let config_monitor = watch_config(&manager);
+    Ok(Daemon { manager, _config_monitor: config_monitor })
+}
+
+/// Run headless until killed, returns the exit status
+pub fn run() -> i32 {
+    let daemon = match start() {
+        Ok(daemon) => daemon,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 1;
+        }
+    };
+
+    let manager = Arc::clone(&daemon.manager);
+    let _owner = gio::bus_own_name(
+        gio::BusType::Session,
+        BUS_NAME,
+        gio::BusNameOwnerFlags::NONE,
+        move |connection, _| {
+            if let Err(e) = dbus::export(&connection, OBJECT_PATH, Arc::clone(&manager)) {
+                eprintln!("Failed to export D-Bus interface: {}", e);
+            }
+        },
+        |_, _| {},
+        |_, name| eprintln!("D-Bus name {} is not available", name),
+    );
+
+    glib::MainLoop::new(None, false).run();
+    0
+}
+
+/// Apply config file changes, from the preferences window or an editor
+fn watch_config(manager: &Arc<SharedClipboardManager>) -> Option<gio::FileMonitor> {
+    let file = gio::File::for_path(Config::path());
+    let monitor = match file.monitor_file(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            eprintln!("Config changes won't be picked up until restart: {}", e);
+            return None;
+        }
+    };
+
+    let manager = Arc::clone(manager);
+    monitor.connect_changed(move |_, _, _, event| {
+        if !matches!(event, gio::FileMonitorEvent::ChangesDoneHint | gio::FileMonitorEvent::Created) {
+            return;
+        }
+        // A half-edited file would otherwise prune the history down to the default limits
+        match Config::try_load() {
+            Ok(config) => manager.0.write().set_config(config),
+            Err(e) => eprintln!("Keeping the current settings, invalid config {}", e),
+        }
+    });
+    Some(monitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn socket_directory_must_be_private() {
+        let base = std::env::temp_dir().join(format!("clipboard_manager-socket-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir(&base).unwrap();
+
+        let private = base.join("private");
+        fs::DirBuilder::new().mode(0o700).create(&private).unwrap();
+        assert!(check_private(&private).is_ok());
+
+        let shared = base.join("shared");
+        fs::DirBuilder::new().mode(0o700).create(&shared).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(check_private(&shared).is_err());
+
+        // Even when it points at a private directory
+        let link = base.join("link");
+        std::os::unix::fs::symlink(&private, &link).unwrap();
+        assert!(check_private(&link).is_err());
+
+        assert!(check_private(&base.join("missing")).is_err());
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,110 @@
+// Messages between the daemon and its clients. Every message is a u32 LE
+// length followed by that many bytes of bincode.
+
+use crate::classify::TextKind;
+use crate::clipboard::{AddOutcome, ManagerEvent};
+use crate::color::Color;
+use crate::models::{ClipboardContent, ClipboardItem, Selection, SourceApp};
+use crate::transform::Transform;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+// Larger than any image the history accepts
+const MAX_MESSAGE_SIZE: usize = 1 << 30;
+
+#[derive(Serialize, Deserialize)]
+pub enum Request {
+    Items,
+    Paste { id: String, selection: Selection },
+    PasteTransformed { id: String, selection: Selection, transform: Transform },
+    SetPinned { id: String, pinned: bool },
+    TogglePin(String),
+    Delete(String),
+    Clear { include_pinned: bool },
+    Add { text: String, sensitive: bool },
+    EditText { id: String, text: String, as_new: bool },
+    ImagePng(String),
+    // Items may only hold the start of long text
+    Content(String),
+    StorageNotice,
+    // The connection only carries Response::Event from then on
+    Subscribe,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum Response {
+    Items(Vec<WireItem>),
+    Done,
+    Found(bool),
+    Removed(usize),
+    Added(AddOutcome),
+    Edited(String),
+    Image(Option<Vec<u8>>),
+    Content(Option<ClipboardContent>),
+    Notice(Option<String>),
+    Event(ManagerEvent),
+    Error(String),
+}
+
+/// An item without its full size image, which is fetched with Request::ImagePng
+#[derive(Serialize, Deserialize)]
+pub struct WireItem {
+    item: ClipboardItem,
+    // ClipboardItem skips these when serializing. Sensitive items need the
+    // expiry to be shown as such, the popup compares hashes to reuse thumbnails.
+    // Truncated text is fetched in full with Request::Content.
+    expires_in_ms: Option<u64>,
+    content_hash: u64,
+    kind: TextKind,
+    color: Option<Color>,
+    source_app: Option<SourceApp>,
+    truncated: bool,
+}
+
+impl WireItem {
+    pub fn new(item: &ClipboardItem) -> Self {
+        let mut item = item.clone();
+        if let ClipboardContent::Image { png_data, .. } = &mut item.content {
+            png_data.clear();
+        }
+        let expires_in_ms = item.expires_at
+            .map(|expires_at| expires_at.saturating_duration_since(Instant::now()).as_millis() as u64);
+        let content_hash = item.content_hash;
+        let (kind, color, source_app) = (item.kind, item.color, item.source_app.clone());
+        let truncated = item.truncated;
+        Self { item, expires_in_ms, content_hash, kind, color, source_app, truncated }
+    }
+
+    pub fn into_item(self) -> ClipboardItem {
+        let mut item = self.item;
+        item.expires_at = self.expires_in_ms.map(|ms| Instant::now() + Duration::from_millis(ms));
+        item.content_hash = self.content_hash;
+        item.kind = self.kind;
+        item.color = self.color;
+        item.source_app = self.source_app;
+        item.truncated = self.truncated;
+        item
+    }
+}
+
+pub fn write_message(stream: &mut impl Write, message: &impl Serialize) -> io::Result<()> {
+    let data = bincode::serialize(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    stream.write_all(&(data.len() as u32).to_le_bytes())?;
+    stream.write_all(&data)?;
+    stream.flush()
+}
+
+pub fn read_message<T: DeserializeOwned>(stream: &mut impl Read) -> io::Result<T> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"));
+    }
+
+    let mut data = vec![0; len];
+    stream.read_exact(&mut data)?;
+    bincode::deserialize(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
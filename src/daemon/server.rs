@@ -0,0 +1,122 @@
+use super::current_uid;
+use super::protocol::{read_message, write_message, Request, Response, WireItem};
+use crate::clipboard::SharedClipboardManager;
+use crate::models::{ClipboardContent, Selection};
+use std::fs;
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+/// Bind the socket and serve every client on its own thread.
+/// Fails if another daemon is already listening.
+pub fn spawn(path: &Path, manager: Arc<SharedClipboardManager>) -> Result<(), String> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("a daemon is already listening on {}", path.display()));
+        }
+        // Left behind by a daemon that didn't exit cleanly, unless someone else put it there
+        let owner = fs::symlink_metadata(path).map(|metadata| metadata.uid());
+        if owner.ok() != Some(current_uid()) {
+            return Err(format!("{} belongs to another user", path.display()));
+        }
+        fs::remove_file(path).map_err(|e| format!("cannot remove {}: {}", path.display(), e))?;
+    }
+
+    // Bound inside a directory only we can enter, so nobody can connect before the chmod
+    let listener = UnixListener::bind(path).map_err(|e| format!("cannot bind {}: {}", path.display(), e))?;
+    // The history may hold secrets, only the owner gets to talk to it
+    if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o600)) {
+        let _ = fs::remove_file(path);
+        return Err(format!("cannot restrict {}: {}", path.display(), e));
+    }
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let manager = Arc::clone(&manager);
+                    thread::spawn(move || {
+                        if let Err(e) = serve(stream, &manager) {
+                            if e.kind() != io::ErrorKind::UnexpectedEof {
+                                eprintln!("Daemon client error: {}", e);
+                            }
+                        }
+                    });
+                }
+                Err(e) => eprintln!("Failed to accept daemon client: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn serve(mut stream: UnixStream, manager: &SharedClipboardManager) -> io::Result<()> {
+    loop {
+        let response = match read_message(&mut stream)? {
+            Request::Subscribe => return stream_events(stream, manager),
+            request => handle(manager, request),
+        };
+        write_message(&mut stream, &response)?;
+    }
+}
+
+fn stream_events(mut stream: UnixStream, manager: &SharedClipboardManager) -> io::Result<()> {
+    let events = manager.0.write().subscribe();
+    write_message(&mut stream, &Response::Done)?;
+    // Ends on the first event after the client went away
+    while let Ok(event) = events.recv() {
+        write_message(&mut stream, &Response::Event(event))?;
+    }
+    Ok(())
+}
+
+fn handle(manager: &SharedClipboardManager, request: Request) -> Response {
+    match request {
+        Request::Items => {
+            let mgr = manager.0.read();
+            Response::Items(mgr.get_items().iter().map(WireItem::new).collect())
+        }
+        Request::Paste { id, selection } => match manager.0.write().paste_item(&id, selection) {
+            Ok(()) => Response::Done,
+            Err(e) => Response::Error(e.to_string()),
+        },
+        Request::PasteTransformed { id, selection, transform } => {
+            match manager.0.write().paste_transformed(&id, selection, transform) {
+                Ok(()) => Response::Done,
+                Err(e) => Response::Error(e),
+            }
+        }
+        Request::SetPinned { id, pinned } => Response::Found(manager.0.write().set_pinned(&id, pinned)),
+        Request::TogglePin(id) => {
+            manager.0.write().toggle_pin(&id);
+            Response::Done
+        }
+        Request::Delete(id) => {
+            let mut mgr = manager.0.write();
+            let found = mgr.get_items().iter().any(|item| item.id == id);
+            mgr.delete_item(&id);
+            Response::Found(found)
+        }
+        Request::Clear { include_pinned } => Response::Removed(manager.0.write().clear(include_pinned)),
+        Request::Add { text, sensitive } => {
+            let content = ClipboardContent::Text(text);
+            let mut mgr = manager.0.write();
+            Response::Added(if sensitive {
+                mgr.add_sensitive_item(content, Selection::Clipboard)
+            } else {
+                mgr.add_item(content, Selection::Clipboard)
+            })
+        }
+        Request::EditText { id, text, as_new } => match manager.0.write().edit_text(&id, text, as_new) {
+            Ok(id) => Response::Edited(id),
+            Err(e) => Response::Error(e),
+        },
+        Request::ImagePng(id) => Response::Image(manager.0.read().image_png(&id)),
+        Request::Content(id) => Response::Content(manager.0.read().content(&id)),
+        Request::StorageNotice => Response::Notice(manager.0.read().take_storage_notice()),
+        Request::Subscribe => unreachable!("subscriptions are handled by serve"),
+    }
+}
@@ -0,0 +1,334 @@
+// Session bus interface for launchers, status bars and scripts. Exported on
+// the application's own connection, but any connection works (a private
+// dbus-daemon in tests, for instance).
+
+use crate::clipboard::{ManagerEvent, PasteError, SharedClipboardManager};
+use crate::models::{ClipboardItem, Selection};
+use crate::search::{filter_items, SearchQuery};
+use chrono::SecondsFormat;
+use gtk4::gio;
+use gtk4::glib::{self, ToVariant};
+use std::sync::Arc;
+use std::thread;
+
+pub const INTERFACE: &str = "com.example.ClipboardManager.History";
+const ERROR_NOT_FOUND: &str = "com.example.ClipboardManager.Error.NotFound";
+const ERROR_FAILED: &str = "com.example.ClipboardManager.Error.Failed";
+
+// Items are (id, kind, RFC 3339 timestamp, pinned, preview), newest first with pinned on top.
+// Search takes the popup's query syntax and sees the first 16 KiB of long items.
+const INTROSPECTION_XML: &str = r#"
+<node>
+  <interface name="com.example.ClipboardManager.History">
+    <method name="GetItems">
+      <arg name="items" type="a(sssbs)" direction="out"/>
+    </method>
+    <method name="Search">
+      <arg name="query" type="s" direction="in"/>
+      <arg name="items" type="a(sssbs)" direction="out"/>
+    </method>
+    <method name="Paste">
+      <arg name="id" type="s" direction="in"/>
+    </method>
+    <method name="Pin">
+      <arg name="id" type="s" direction="in"/>
+      <arg name="pinned" type="b" direction="in"/>
+    </method>
+    <method name="Delete">
+      <arg name="id" type="s" direction="in"/>
+    </method>
+    <method name="Clear">
+      <arg name="include_pinned" type="b" direction="in"/>
+      <arg name="removed" type="u" direction="out"/>
+    </method>
+    <signal name="ItemAdded">
+      <arg name="id" type="s"/>
+    </signal>
+    <signal name="ItemRemoved">
+      <arg name="id" type="s"/>
+    </signal>
+    <signal name="ItemChanged">
+      <arg name="id" type="s"/>
+    </signal>
+  </interface>
+</node>
+"#;
+
+type ItemTuple = (String, String, String, bool, String);
+
+/// Register the interface at `object_path` and start emitting its signals
+pub fn export(
+    connection: &gio::DBusConnection,
+    object_path: &str,
+    manager: Arc<SharedClipboardManager>,
+) -> Result<gio::RegistrationId, glib::Error> {
+    let node = gio::DBusNodeInfo::for_xml(INTROSPECTION_XML)?;
+    let interface = node.lookup_interface(INTERFACE).expect("interface in introspection data");
+
+    let manager_calls = Arc::clone(&manager);
+    // The interface has no properties, GDBus never asks for them
+    let registration = connection.register_object(
+        object_path,
+        &interface,
+        move |_, _, _, _, method, parameters, invocation| {
+            handle_call(&manager_calls, method, &parameters, invocation);
+        },
+        |_, _, _, _, _| ().to_variant(),
+        |_, _, _, _, _, _| false,
+    )?;
+
+    forward_events(connection, object_path, &manager);
+    Ok(registration)
+}
+
+fn handle_call(
+    manager: &SharedClipboardManager,
+    method: &str,
+    parameters: &glib::Variant,
+    invocation: gio::DBusMethodInvocation,
+) {
+    match method {
+        "GetItems" => {
+            let mgr = manager.0.read();
+            let items: Vec<ItemTuple> = mgr.get_items().iter().map(item_tuple).collect();
+            invocation.return_value(Some(&(items,).to_variant()));
+        }
+        "Search" => {
+            let Some((query,)) = parameters.get::<(String,)>() else {
+                return invocation.return_dbus_error(ERROR_FAILED, "expected (s)");
+            };
+            let mgr = manager.0.read();
+            let items: Vec<ItemTuple> = filter_items(mgr.get_items(), &SearchQuery::parse(&query))
+                .into_iter()
+                .map(item_tuple)
+                .collect();
+            invocation.return_value(Some(&(items,).to_variant()));
+        }
+        "Paste" => {
+            let Some((id,)) = parameters.get::<(String,)>() else {
+                return invocation.return_dbus_error(ERROR_FAILED, "expected (s)");
+            };
+            match manager.0.write().paste_item(&id, Selection::Clipboard) {
+                Ok(()) => invocation.return_value(None),
+                Err(PasteError::NotFound) => {
+                    invocation.return_dbus_error(ERROR_NOT_FOUND, &format!("no item with id '{}'", id))
+                }
+                Err(PasteError::Failed(e)) => invocation.return_dbus_error(ERROR_FAILED, &e),
+            }
+        }
+        "Pin" => {
+            let Some((id, pinned)) = parameters.get::<(String, bool)>() else {
+                return invocation.return_dbus_error(ERROR_FAILED, "expected (sb)");
+            };
+            if manager.0.write().set_pinned(&id, pinned) {
+                invocation.return_value(None);
+            } else {
+                invocation.return_dbus_error(ERROR_NOT_FOUND, &format!("no item with id '{}'", id));
+            }
+        }
+        "Delete" => {
+            let Some((id,)) = parameters.get::<(String,)>() else {
+                return invocation.return_dbus_error(ERROR_FAILED, "expected (s)");
+            };
+            let mut mgr = manager.0.write();
+            if mgr.get_items().iter().any(|item| item.id == id) {
+                mgr.delete_item(&id);
+                invocation.return_value(None);
+            } else {
+                invocation.return_dbus_error(ERROR_NOT_FOUND, &format!("no item with id '{}'", id));
+            }
+        }
+        "Clear" => {
+            let Some((include_pinned,)) = parameters.get::<(bool,)>() else {
+                return invocation.return_dbus_error(ERROR_FAILED, "expected (b)");
+            };
+            let removed = manager.0.write().clear(include_pinned) as u32;
+            invocation.return_value(Some(&(removed,).to_variant()));
+        }
+        _ => invocation.return_dbus_error(
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            &format!("unknown method {}", method),
+        ),
+    }
+}
+
+fn item_tuple(item: &ClipboardItem) -> ItemTuple {
+    (
+        item.id.clone(),
+        item.content.kind_name().to_string(),
+        item.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+        item.pinned,
+        item.preview(),
+    )
+}
+
+/// Emit ItemAdded/ItemRemoved/ItemChanged from the main loop for every manager event
+fn forward_events(connection: &gio::DBusConnection, object_path: &str, manager: &SharedClipboardManager) {
+    let events = manager.0.write().subscribe();
+    let (sender, receiver) = async_channel::unbounded();
+    thread::spawn(move || {
+        while let Ok(event) = events.recv() {
+            if sender.send_blocking(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let connection = connection.clone();
+    let object_path = object_path.to_string();
+    glib::spawn_future_local(async move {
+        while let Ok(event) = receiver.recv().await {
+            let (signal, id) = match event {
+                ManagerEvent::Added(id) => ("ItemAdded", id),
+                ManagerEvent::Removed(id) => ("ItemRemoved", id),
+                ManagerEvent::Changed(id) => ("ItemChanged", id),
+            };
+            if let Err(e) = connection.emit_signal(None, &object_path, INTERFACE, signal, Some(&(id,).to_variant())) {
+                eprintln!("Failed to emit {}: {}", signal, e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipboard::{AddOutcome, ClipboardManager};
+    use crate::config::Config;
+    use crate::models::ClipboardContent;
+    use crate::storage::Storage;
+    use parking_lot::RwLock;
+    use std::cell::RefCell;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    const OBJECT_PATH: &str = "/com/example/ClipboardManager/Test";
+
+    fn call(
+        context: &glib::MainContext,
+        connection: &gio::DBusConnection,
+        destination: &str,
+        method: &str,
+        parameters: Option<glib::Variant>,
+    ) -> Result<glib::Variant, glib::Error> {
+        context.block_on(connection.call_future(
+            Some(destination),
+            OBJECT_PATH,
+            INTERFACE,
+            method,
+            parameters.as_ref(),
+            None,
+            gio::DBusCallFlags::NONE,
+            5000,
+        ))
+    }
+
+    fn items(reply: glib::Variant) -> Vec<ItemTuple> {
+        reply.get::<(Vec<ItemTuple>,)>().expect("a(sssbs)").0
+    }
+
+    fn not_found(result: Result<glib::Variant, glib::Error>) -> bool {
+        result.is_err_and(|e| e.message().contains(ERROR_NOT_FOUND))
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon, run with --ignored"]
+    fn methods_and_signals_on_a_private_bus() {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("dbus-daemon on PATH");
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap()).read_line(&mut address).unwrap();
+
+        // Keep the history away from the user's
+        let home = std::env::temp_dir().join(format!("clipboard_manager-dbus-{}", std::process::id()));
+        let config = Config::default();
+        let storage = Storage::with_dir(home.clone(), &config.encryption);
+        let manager = Arc::new(SharedClipboardManager(RwLock::new(ClipboardManager::with_storage(config, storage))));
+        let alpha = manager.0.write().add_item(ClipboardContent::Text("alpha".into()), Selection::Clipboard);
+        let beta = manager.0.write().add_item(ClipboardContent::Text("beta".into()), Selection::Clipboard);
+        let (AddOutcome::Added(alpha), AddOutcome::Added(beta)) = (alpha, beta) else {
+            panic!("items weren't added");
+        };
+
+        let context = glib::MainContext::new();
+        context.with_thread_default(|| {
+            let flags = gio::DBusConnectionFlags::AUTHENTICATION_CLIENT | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION;
+            let connect = || {
+                gio::DBusConnection::for_address_sync(address.trim(), flags, None, gio::Cancellable::NONE).unwrap()
+            };
+            let server = connect();
+            let client = connect();
+            export(&server, OBJECT_PATH, Arc::clone(&manager)).unwrap();
+            let destination = server.unique_name().unwrap();
+
+            let signals = Rc::new(RefCell::new(Vec::new()));
+            let received = Rc::clone(&signals);
+            client.signal_subscribe(
+                Some(&destination),
+                Some(INTERFACE),
+                None,
+                Some(OBJECT_PATH),
+                None,
+                gio::DBusSignalFlags::NONE,
+                move |_, _, _, _, signal, parameters| {
+                    received.borrow_mut().push((signal.to_string(), parameters.get::<(String,)>().unwrap().0));
+                },
+            );
+            let wait_for_signals = |count: usize| {
+                let deadline = Instant::now() + Duration::from_secs(5);
+                while signals.borrow().len() < count && Instant::now() < deadline {
+                    context.iteration(false);
+                }
+                signals.take()
+            };
+
+            let listed = items(call(&context, &client, &destination, "GetItems", None).unwrap());
+            assert_eq!(listed.iter().map(|item| item.0.as_str()).collect::<Vec<_>>(), [&beta, &alpha]);
+            assert_eq!((listed[0].1.as_str(), listed[0].3, listed[0].4.as_str()), ("text", false, "beta"));
+
+            let found = items(call(&context, &client, &destination, "Search", Some(("alp",).to_variant())).unwrap());
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].0, alpha);
+
+            call(&context, &client, &destination, "Pin", Some((&alpha, true).to_variant())).unwrap();
+            let listed = items(call(&context, &client, &destination, "GetItems", None).unwrap());
+            assert_eq!(listed[0].0, alpha);
+            assert!(listed[0].3);
+            assert!(not_found(call(&context, &client, &destination, "Pin", Some(("missing", true).to_variant()))));
+
+            assert!(not_found(call(&context, &client, &destination, "Paste", Some(("missing",).to_variant()))));
+
+            call(&context, &client, &destination, "Delete", Some((&beta,).to_variant())).unwrap();
+            assert!(not_found(call(&context, &client, &destination, "Delete", Some((&beta,).to_variant()))));
+            assert_eq!(wait_for_signals(1), [("ItemRemoved".to_string(), beta.clone())]);
+
+            // Changes made by the daemon itself
+            let gamma = manager.0.write().add_item(ClipboardContent::Text("gamma".into()), Selection::Clipboard);
+            let AddOutcome::Added(gamma) = gamma else {
+                panic!("item wasn't added");
+            };
+            manager.0.write().edit_text(&gamma, "gamma 2".into(), false).unwrap();
+            assert_eq!(
+                wait_for_signals(2),
+                [("ItemAdded".to_string(), gamma.clone()), ("ItemChanged".to_string(), gamma.clone())],
+            );
+            let found = items(call(&context, &client, &destination, "Search", Some(("gamma 2",).to_variant())).unwrap());
+            assert_eq!((found.len(), found[0].0.as_str()), (1, gamma.as_str()));
+
+            let cleared = call(&context, &client, &destination, "Clear", Some((false,).to_variant())).unwrap();
+            assert_eq!(cleared.get::<(u32,)>(), Some((1,)));
+            let cleared = call(&context, &client, &destination, "Clear", Some((true,).to_variant())).unwrap();
+            assert_eq!(cleared.get::<(u32,)>(), Some((1,)));
+            assert!(items(call(&context, &client, &destination, "GetItems", None).unwrap()).is_empty());
+        }).unwrap();
+
+        let _ = daemon.kill();
+        let _ = daemon.wait();
+        let _ = std::fs::remove_dir_all(home);
+    }
+}
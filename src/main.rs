@@ -1,38 +1,92 @@
+mod classify;
+mod autopaste;
+mod cli;
 mod clipboard;
+mod color;
+mod config;
+mod crypto;
+mod daemon;
+mod dbus;
+mod mime;
+mod search;
+mod sensitive;
 mod storage;
+mod transform;
 mod ui;
 mod models;
+mod owner;
+mod watcher;
+mod x11;
 
-use gtk4::prelude::*; 
+use daemon::DaemonClient;
+use gtk4::prelude::*;
 use libadwaita as adw;
+use once_cell::unsync::OnceCell;
 use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
 
 const APP_ID: &str = "com.example.ClipboardManager";
 
 fn main() {
+    if std::env::args().nth(1).as_deref() == Some("--daemon") {
+        std::process::exit(daemon::run());
+    }
+
     let app = adw::Application::builder()
         .application_id(APP_ID)
         .flags(gtk4::gio::ApplicationFlags::HANDLES_COMMAND_LINE)
         .build();
-    
+
     let window_ref: RefCell<Option<adw::ApplicationWindow>> = RefCell::new(None);
-    
-    app.connect_command_line(|app, _| {
-        app.activate();
-        0
+
+    // Only connected in the primary instance, remote invocations just forward their arguments
+    let client: Rc<OnceCell<Arc<DaemonClient>>> = Rc::new(OnceCell::new());
+
+    let client_startup = Rc::clone(&client);
+    let embedded = OnceCell::new();
+    app.connect_startup(move |app| {
+        sourceview5::init();
+        let connected = DaemonClient::connect().or_else(|_| {
+            // No daemon running, capture in this process for as long as it runs
+            let daemon = daemon::start()?;
+            if let (Some(connection), Some(path)) = (app.dbus_connection(), app.dbus_object_path()) {
+                if let Err(e) = dbus::export(&connection, &path, Arc::clone(&daemon.manager)) {
+                    eprintln!("Failed to export D-Bus interface: {}", e);
+                }
+            }
+            let _ = embedded.set(daemon);
+            DaemonClient::connect()
+        });
+        match connected {
+            Ok(connected) => {
+                let _ = client_startup.set(Arc::new(connected));
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        }
     });
-    
+
+    let client_cli = Rc::clone(&client);
+    app.connect_command_line(move |app, cmdline| {
+        let client = client_cli.get().expect("connected on startup");
+        cli::handle_command_line(app, cmdline, client)
+    });
+
     app.connect_activate(move |app| {
         let mut window_opt = window_ref.borrow_mut();
-        
+
         let window = if let Some(win) = window_opt.as_ref() {
             win.clone()
         } else {
-            let win = ui::window::build_ui(app);
+            let client = client.get().expect("connected on startup");
+            let win = ui::window::build_ui(app, Arc::clone(client));
             *window_opt = Some(win.clone());
             win
         };
-        
+
         if window.is_visible() {
             window.set_visible(false);
         } else {
@@ -41,4 +95,4 @@ fn main() {
     });
 
     app.run();
-}
\ No newline at end of file
+}
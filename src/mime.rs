@@ -0,0 +1,341 @@
+// Clipboard formats arboard doesn't cover (HTML, RTF, file lists). Reads and
+// writes go through data-control on Wayland and plain selection requests on X11.
+
+use crate::models::Selection;
+use std::time::Duration;
+
+pub const TEXT_PLAIN: &str = "text/plain;charset=utf-8";
+pub const HTML: &str = "text/html";
+pub const RTF: &[&str] = &["text/rtf", "application/rtf", "text/richtext"];
+pub const URI_LIST: &str = "text/uri-list";
+pub const GNOME_COPIED_FILES: &str = "x-special/gnome-copied-files";
+pub const PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
+
+// Owners that never answer shouldn't stall the watcher thread
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// MIME types currently offered on the selection
+pub fn offered_types(selection: Selection) -> Vec<String> {
+    if is_wayland() {
+        if let Ok(types) = wayland::offered_types(selection) {
+            return types;
+        }
+    }
+    x11::offered_types(selection).unwrap_or_default()
+}
+
+/// Read one representation of the current selection content
+pub fn read(selection: Selection, mime: &str) -> Option<Vec<u8>> {
+    if is_wayland() {
+        if let Ok(data) = wayland::read(selection, mime) {
+            return Some(data);
+        }
+    }
+    x11::read(selection, mime).ok()
+}
+
+/// Take the selection, offering every representation at once
+pub fn write(selection: Selection, representations: Vec<(String, Vec<u8>)>) -> Result<(), String> {
+    if is_wayland() && wayland::write(selection, representations.clone()).is_ok() {
+        return Ok(());
+    }
+    x11::write(selection, representations)
+}
+
+/// Parse a `text/uri-list` payload
+pub fn parse_uri_list(data: &str) -> Vec<String> {
+    data.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// Local path for `file://` URIs, the URI itself otherwise
+pub fn uri_to_path(uri: &str) -> String {
+    match uri.strip_prefix("file://") {
+        Some(rest) => {
+            // Skip the (usually empty) host part
+            let path = rest.find('/').map(|i| &rest[i..]).unwrap_or(rest);
+            percent_decode(path)
+        }
+        None => uri.to_string(),
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn is_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+fn err(e: impl std::fmt::Display) -> String {
+    e.to_string()
+}
+
+mod wayland {
+    use super::err;
+    use crate::models::Selection;
+    use std::io::Read;
+    use wl_clipboard_rs::copy::{self, MimeSource, Options, Source};
+    use wl_clipboard_rs::paste::{self, get_contents, get_mime_types, ClipboardType, Seat};
+
+    fn clipboard_type(selection: Selection) -> ClipboardType {
+        match selection {
+            Selection::Clipboard => ClipboardType::Regular,
+            Selection::Primary => ClipboardType::Primary,
+        }
+    }
+
+    pub fn offered_types(selection: Selection) -> Result<Vec<String>, String> {
+        let types = get_mime_types(clipboard_type(selection), Seat::Unspecified).map_err(err)?;
+        Ok(types.into_iter().collect())
+    }
+
+    pub fn read(selection: Selection, mime: &str) -> Result<Vec<u8>, String> {
+        let (mut pipe, _) = get_contents(clipboard_type(selection), Seat::Unspecified, paste::MimeType::Specific(mime))
+            .map_err(err)?;
+        let mut data = Vec::new();
+        pipe.read_to_end(&mut data).map_err(err)?;
+        Ok(data)
+    }
+
+    pub fn write(selection: Selection, representations: Vec<(String, Vec<u8>)>) -> Result<(), String> {
+        let sources = representations.into_iter()
+            .map(|(mime, data)| MimeSource {
+                source: Source::Bytes(data.into_boxed_slice()),
+                mime_type: copy::MimeType::Specific(mime),
+            })
+            .collect();
+        let mut options = Options::new();
+        options.clipboard(match selection {
+            Selection::Clipboard => copy::ClipboardType::Regular,
+            Selection::Primary => copy::ClipboardType::Primary,
+        });
+        // Serves paste requests from a background thread until someone else copies
+        options.copy_multi(sources).map_err(err)
+    }
+}
+
+mod x11 {
+    use super::{err, READ_TIMEOUT};
+    use crate::models::Selection as SelectionKind;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use x11rb::connection::{Connection, RequestConnection};
+    use x11rb::protocol::xproto::{
+        Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, Property,
+        SelectionNotifyEvent, Window, WindowClass, SELECTION_NOTIFY_EVENT,
+    };
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::wrapper::ConnectionExt as _;
+
+    struct Selection {
+        conn: RustConnection,
+        window: Window,
+        selection: Atom,
+        property: Atom,
+    }
+
+    impl Selection {
+        fn connect(kind: SelectionKind) -> Result<Self, String> {
+            let (conn, screen_num) = x11rb::connect(None).map_err(err)?;
+            let root = conn.setup().roots[screen_num].root;
+
+            let window = conn.generate_id().map_err(err)?;
+            conn.create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                0, 0, 1, 1, 0,
+                WindowClass::INPUT_OUTPUT,
+                x11rb::COPY_FROM_PARENT,
+                &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+            ).map_err(err)?;
+
+            let mut selection = Self { conn, window, selection: 0, property: 0 };
+            selection.selection = selection.intern(match kind {
+                SelectionKind::Clipboard => "CLIPBOARD",
+                SelectionKind::Primary => "PRIMARY",
+            })?;
+            selection.property = selection.intern("CLIPBOARD_MANAGER_DATA")?;
+            Ok(selection)
+        }
+
+        fn intern(&self, name: &str) -> Result<Atom, String> {
+            Ok(self.conn.intern_atom(false, name.as_bytes()).map_err(err)?.reply().map_err(err)?.atom)
+        }
+
+        fn convert(&self, target: Atom) -> Result<Vec<u8>, String> {
+            self.conn.convert_selection(self.window, self.selection, target, self.property, x11rb::CURRENT_TIME)
+                .map_err(err)?;
+            self.conn.flush().map_err(err)?;
+
+            let property = self.wait_for(|event| match event {
+                Event::SelectionNotify(ev) if ev.requestor == self.window => Some(ev.property),
+                _ => None,
+            })?;
+            if property == x11rb::NONE {
+                return Err("selection owner refused the conversion".to_string());
+            }
+
+            let reply = self.conn
+                .get_property(true, self.window, self.property, AtomEnum::ANY, 0, u32::MAX / 4)
+                .map_err(err)?
+                .reply()
+                .map_err(err)?;
+
+            if reply.type_ == self.intern("INCR")? {
+                return self.read_incremental();
+            }
+            Ok(reply.value)
+        }
+
+        // Large transfers arrive in chunks, each announced by a property change
+        fn read_incremental(&self) -> Result<Vec<u8>, String> {
+            let mut data = Vec::new();
+            loop {
+                self.wait_for(|event| match event {
+                    Event::PropertyNotify(ev)
+                        if ev.atom == self.property && ev.state == Property::NEW_VALUE => Some(()),
+                    _ => None,
+                })?;
+
+                let chunk = self.conn
+                    .get_property(true, self.window, self.property, AtomEnum::ANY, 0, u32::MAX / 4)
+                    .map_err(err)?
+                    .reply()
+                    .map_err(err)?;
+                if chunk.value.is_empty() {
+                    return Ok(data);
+                }
+                data.extend_from_slice(&chunk.value);
+            }
+        }
+
+        fn wait_for<T>(&self, mut matches: impl FnMut(&Event) -> Option<T>) -> Result<T, String> {
+            let deadline = Instant::now() + READ_TIMEOUT;
+            while Instant::now() < deadline {
+                match self.conn.poll_for_event().map_err(err)? {
+                    Some(event) => {
+                        if let Some(result) = matches(&event) {
+                            return Ok(result);
+                        }
+                    }
+                    None => thread::sleep(Duration::from_millis(5)),
+                }
+            }
+            Err("timed out waiting for the selection owner".to_string())
+        }
+    }
+
+    pub fn offered_types(kind: SelectionKind) -> Result<Vec<String>, String> {
+        let selection = Selection::connect(kind)?;
+        let targets = selection.convert(selection.intern("TARGETS")?)?;
+
+        let mut types = Vec::new();
+        for chunk in targets.chunks_exact(4) {
+            let atom = u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            if let Ok(reply) = selection.conn.get_atom_name(atom).map_err(err)?.reply() {
+                types.push(String::from_utf8_lossy(&reply.name).into_owned());
+            }
+        }
+        Ok(types)
+    }
+
+    pub fn read(kind: SelectionKind, mime: &str) -> Result<Vec<u8>, String> {
+        let selection = Selection::connect(kind)?;
+        selection.convert(selection.intern(mime)?)
+    }
+
+    pub fn write(kind: SelectionKind, representations: Vec<(String, Vec<u8>)>) -> Result<(), String> {
+        let selection = Selection::connect(kind)?;
+        let targets = selection.intern("TARGETS")?;
+
+        let mut offered: Vec<(Atom, Vec<u8>)> = Vec::new();
+        for (mime, data) in representations {
+            // Older X11 clients only ask for the legacy text targets
+            if mime.starts_with("text/plain") {
+                for name in ["UTF8_STRING", "STRING", "TEXT"] {
+                    offered.push((selection.intern(name)?, data.clone()));
+                }
+            }
+            offered.push((selection.intern(&mime)?, data));
+        }
+
+        selection.conn
+            .set_selection_owner(selection.window, selection.selection, x11rb::CURRENT_TIME)
+            .map_err(err)?;
+        let owner = selection.conn.get_selection_owner(selection.selection)
+            .map_err(err)?
+            .reply()
+            .map_err(err)?
+            .owner;
+        if owner != selection.window {
+            return Err("could not take ownership of the selection".to_string());
+        }
+
+        thread::spawn(move || serve(selection, targets, offered));
+        Ok(())
+    }
+
+    // Answer paste requests until another client takes the clipboard
+    fn serve(selection: Selection, targets: Atom, offered: Vec<(Atom, Vec<u8>)>) {
+        let conn = &selection.conn;
+        // No INCR support when serving, refuse what doesn't fit in one request
+        let max_bytes = conn.maximum_request_bytes().saturating_sub(64);
+        let mut target_list: Vec<Atom> = offered.iter().map(|(atom, _)| *atom).collect();
+        target_list.push(targets);
+
+        loop {
+            let request = match conn.wait_for_event() {
+                Ok(Event::SelectionRequest(request)) => request,
+                Ok(Event::SelectionClear(_)) | Err(_) => return,
+                Ok(_) => continue,
+            };
+
+            let property = if request.property == x11rb::NONE { request.target } else { request.property };
+            let stored = if request.target == targets {
+                conn.change_property32(PropMode::REPLACE, request.requestor, property, AtomEnum::ATOM, &target_list)
+                    .is_ok()
+            } else {
+                match offered.iter().find(|(atom, _)| *atom == request.target) {
+                    Some((_, data)) if data.len() <= max_bytes => conn
+                        .change_property8(PropMode::REPLACE, request.requestor, property, request.target, data)
+                        .is_ok(),
+                    _ => false,
+                }
+            };
+
+            let notify = SelectionNotifyEvent {
+                response_type: SELECTION_NOTIFY_EVENT,
+                sequence: 0,
+                time: request.time,
+                requestor: request.requestor,
+                selection: request.selection,
+                target: request.target,
+                property: if stored { property } else { x11rb::NONE },
+            };
+            let _ = conn.send_event(false, request.requestor, EventMask::NO_EVENT, notify);
+            let _ = conn.flush();
+        }
+    }
+}
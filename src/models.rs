@@ -1,17 +1,16 @@
 use serde::{Deserialize, Serialize};
-use chrono::Local;
+use crate::classify::TextKind;
+use crate::color::Color;
+use chrono::{DateTime, Utc};
 use std::hash::{Hash, Hasher};
-
-pub const MAX_ITEMS: usize = 50;
-pub const MAX_TEXT_SIZE: usize = 4 * 1024 * 1024; // 4MB for text
-pub const MAX_IMAGE_SIZE: usize = 50 * 1024 * 1024; // 50MB for images
-pub const THUMBNAIL_SIZE: u32 = 80; // Thumbnail dimension
+use std::time::Instant;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ClipboardContent {
     Text(String),
     Image {
-        // Store full image as PNG bytes
+        // Store full image as PNG bytes. Empty for items loaded from the
+        // database, see ClipboardManager::image_png
         png_data: Vec<u8>,
         // Pre-generated thumbnail for fast display
         thumbnail_png: Vec<u8>,
@@ -19,6 +18,89 @@ pub enum ClipboardContent {
         width: u32,
         height: u32,
     },
+    // Rich text keeps the plain text the source offered alongside it
+    Html {
+        html: String,
+        text: String,
+    },
+    Rtf {
+        rtf: String,
+        text: String,
+    },
+    // URIs from text/uri-list, usually file:// from a file manager
+    Files(Vec<String>),
+}
+
+impl ClipboardContent {
+    /// Plain text representation, None for images
+    pub fn plain_text(&self) -> Option<String> {
+        match self {
+            ClipboardContent::Text(text) => Some(text.clone()),
+            ClipboardContent::Html { text, .. } | ClipboardContent::Rtf { text, .. } => Some(text.clone()),
+            ClipboardContent::Files(uris) => Some(
+                uris.iter().map(|uri| crate::mime::uri_to_path(uri)).collect::<Vec<_>>().join("\n"),
+            ),
+            ClipboardContent::Image { .. } => None,
+        }
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            ClipboardContent::Text(_) => "text",
+            ClipboardContent::Image { .. } => "image",
+            ClipboardContent::Html { .. } => "html",
+            ClipboardContent::Rtf { .. } => "rtf",
+            ClipboardContent::Files(_) => "files",
+        }
+    }
+
+    /// The content cut down to about `max` bytes of text, None if it's within that already
+    pub fn excerpt(&self, max: usize) -> Option<ClipboardContent> {
+        if self.size() <= max {
+            return None;
+        }
+        Some(match self {
+            ClipboardContent::Text(text) => ClipboardContent::Text(truncate(text, max)),
+            ClipboardContent::Html { html, text } => ClipboardContent::Html {
+                html: truncate(html, max),
+                text: truncate(text, max),
+            },
+            ClipboardContent::Rtf { rtf, text } => ClipboardContent::Rtf {
+                rtf: truncate(rtf, max),
+                text: truncate(text, max),
+            },
+            ClipboardContent::Files(uris) => {
+                let mut total = 0;
+                ClipboardContent::Files(uris.iter()
+                    .take_while(|uri| {
+                        total += uri.len();
+                        total <= max
+                    })
+                    .cloned()
+                    .collect())
+            }
+            ClipboardContent::Image { .. } => return None,
+        })
+    }
+
+    /// Size checked against the `[history]` size limits
+    pub fn size(&self) -> usize {
+        match self {
+            ClipboardContent::Text(text) => text.len(),
+            ClipboardContent::Image { png_data, .. } => png_data.len(),
+            ClipboardContent::Html { html, text } => html.len() + text.len(),
+            ClipboardContent::Rtf { rtf, text } => rtf.len() + text.len(),
+            ClipboardContent::Files(uris) => uris.iter().map(|uri| uri.len()).sum(),
+        }
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    let mut end = text.len().min(max);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[..end].to_string()
 }
 
 impl PartialEq for ClipboardContent {
@@ -38,6 +120,9 @@ impl PartialEq for ClipboardContent {
                     a == b
                 }
             }
+            (ClipboardContent::Html { html: a, .. }, ClipboardContent::Html { html: b, .. }) => a == b,
+            (ClipboardContent::Rtf { rtf: a, .. }, ClipboardContent::Rtf { rtf: b, .. }) => a == b,
+            (ClipboardContent::Files(a), ClipboardContent::Files(b)) => a == b,
             _ => false,
         }
     }
@@ -58,41 +143,103 @@ impl Hash for ClipboardContent {
                 height.hash(state);
                 png_data.len().hash(state);
                 // Hash first and last chunks for speed
-                if png_data.len() > 0 {
+                if !png_data.is_empty() {
                     png_data[..png_data.len().min(1024)].hash(state);
                     if png_data.len() > 1024 {
                         png_data[png_data.len() - 1024..].hash(state);
                     }
                 }
             }
+            ClipboardContent::Html { html, .. } => {
+                2u8.hash(state);
+                html.hash(state);
+            }
+            ClipboardContent::Rtf { rtf, .. } => {
+                3u8.hash(state);
+                rtf.hash(state);
+            }
+            ClipboardContent::Files(uris) => {
+                4u8.hash(state);
+                uris.hash(state);
+            }
         }
     }
 }
 
+/// Which X11/Wayland selection an item was copied from or is pasted to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Selection {
+    #[default]
+    Clipboard,
+    // Middle-click paste
+    Primary,
+}
+
+/// Application a copy came from, as far as the desktop tells
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceApp {
+    // WM_CLASS class (or process name), also looked up as icon name
+    pub id: String,
+    // Title of its window at the time of the copy
+    pub title: Option<String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ClipboardItem {
     pub content: ClipboardContent,
-    pub timestamp: String,
+    pub timestamp: DateTime<Utc>,
     pub pinned: bool,
     pub id: String,
+    pub source: Selection,
     #[serde(skip)]
     pub content_hash: u64,
+    // Detected from the text, Plain for everything else
+    #[serde(skip)]
+    pub kind: TextKind,
+    // Parsed value of color items, for swatches and conversions
+    #[serde(skip)]
+    pub color: Option<Color>,
+    // Stored in its own column, None where the desktop doesn't tell
+    #[serde(skip)]
+    pub source_app: Option<SourceApp>,
+    // Bytes stored for the item, kept since images are loaded without their full data
+    #[serde(skip)]
+    pub size: usize,
+    // Set for sensitive items, which are kept in memory only until then
+    #[serde(skip)]
+    pub expires_at: Option<Instant>,
+    // Only the start of the text is in memory, see ClipboardManager::content
+    #[serde(skip)]
+    pub truncated: bool,
 }
 
 impl ClipboardItem {
-    pub fn new(content: ClipboardContent) -> Self {
+    pub fn new(content: ClipboardContent, source: Selection) -> Self {
         use std::collections::hash_map::DefaultHasher;
         
         let mut hasher = DefaultHasher::new();
         content.hash(&mut hasher);
         let content_hash = hasher.finish();
+        let size = content.size();
+        let kind = TextKind::of(&content);
+        let color = match &content {
+            ClipboardContent::Text(text) if kind == TextKind::Color => Color::parse(text),
+            _ => None,
+        };
         
         Self {
             content,
-            timestamp: Local::now().format("%H:%M:%S").to_string(),
+            timestamp: Utc::now(),
             pinned: false,
             id: uuid::Uuid::new_v4().to_string(),
+            source,
             content_hash,
+            kind,
+            color,
+            source_app: None,
+            size,
+            expires_at: None,
+            truncated: false,
         }
     }
 
@@ -104,6 +251,20 @@ impl ClipboardItem {
         self.content_hash = hasher.finish();
     }
     
+    /// Let go of what storage can give back: the full size image and text
+    /// beyond `max_text` bytes. The hash, kind and size stay those of the full content.
+    pub fn unload(&mut self, max_text: usize) {
+        match &mut self.content {
+            ClipboardContent::Image { png_data, .. } => *png_data = Vec::new(),
+            content => {
+                if let Some(excerpt) = content.excerpt(max_text) {
+                    *content = excerpt;
+                    self.truncated = true;
+                }
+            }
+        }
+    }
+
     pub fn is_image(&self) -> bool {
         matches!(self.content, ClipboardContent::Image { .. })
     }
@@ -111,4 +272,26 @@ impl ClipboardItem {
     pub fn is_text(&self) -> bool {
         matches!(self.content, ClipboardContent::Text(_))
     }
+
+    #[inline]
+    pub fn is_sensitive(&self) -> bool {
+        self.expires_at.is_some()
+    }
+
+    /// Single line summary for listings, secrets are masked
+    pub fn preview(&self) -> String {
+        match &self.content {
+            _ if self.is_sensitive() => "[sensitive]".to_string(),
+            ClipboardContent::Image { width, height, .. } => format!("[image {}×{}]", width, height),
+            content => {
+                let text = content.plain_text().unwrap_or_default();
+                let line: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if line.chars().count() > 80 {
+                    format!("{}...", line.chars().take(80).collect::<String>())
+                } else {
+                    line
+                }
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,114 @@
+// Which application owns a selection. X11 tells through the owner window;
+// Wayland's data-control protocol doesn't say who offered a selection.
+
+use crate::models::{Selection, SourceApp};
+
+fn is_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+fn err(e: impl std::fmt::Display) -> String {
+    e.to_string()
+}
+
+/// Application currently owning `selection`, None if it can't be told
+pub fn selection_owner(selection: Selection) -> Option<SourceApp> {
+    if is_wayland() {
+        return None;
+    }
+    x11::selection_owner(selection).unwrap_or_else(|e| {
+        eprintln!("Cannot tell the selection owner: {}", e);
+        None
+    })
+}
+
+mod x11 {
+    use super::err;
+    use crate::models::{Selection, SourceApp};
+    use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt as _, Window};
+    use x11rb::rust_connection::RustConnection;
+
+    struct Atoms {
+        utf8_string: Atom,
+        net_wm_name: Atom,
+        net_wm_pid: Atom,
+        wm_client_leader: Atom,
+        net_active_window: Atom,
+    }
+
+    pub fn selection_owner(selection: Selection) -> Result<Option<SourceApp>, String> {
+        crate::x11::with_connection(|conn, root| lookup(conn, root, selection))
+    }
+
+    fn lookup(conn: &RustConnection, root: Window, selection: Selection) -> Result<Option<SourceApp>, String> {
+        let intern = |name: &[u8]| -> Result<Atom, String> {
+            Ok(conn.intern_atom(false, name).map_err(err)?.reply().map_err(err)?.atom)
+        };
+
+        let selection = intern(match selection {
+            Selection::Clipboard => b"CLIPBOARD",
+            Selection::Primary => b"PRIMARY",
+        })?;
+        let owner = conn.get_selection_owner(selection).map_err(err)?.reply().map_err(err)?.owner;
+        if owner == x11rb::NONE {
+            return Ok(None);
+        }
+
+        let atoms = Atoms {
+            utf8_string: intern(b"UTF8_STRING")?,
+            net_wm_name: intern(b"_NET_WM_NAME")?,
+            net_wm_pid: intern(b"_NET_WM_PID")?,
+            wm_client_leader: intern(b"WM_CLIENT_LEADER")?,
+            net_active_window: intern(b"_NET_ACTIVE_WINDOW")?,
+        };
+
+        // Toolkits own selections with hidden windows, which point to their
+        // main window through WM_CLIENT_LEADER at best
+        let leader = window_property(conn, owner, atoms.wm_client_leader, AtomEnum::WINDOW.into());
+        let active = window_property(conn, root, atoms.net_active_window, AtomEnum::WINDOW.into());
+        let candidates = [Some(owner), leader].into_iter().flatten();
+
+        for window in candidates {
+            if let Some(class) = wm_class(conn, window) {
+                // Hidden windows have no title, the focused one of the same app does
+                let title = title(conn, &atoms, window).or_else(|| {
+                    active.filter(|&active| wm_class(conn, active).as_deref() == Some(class.as_str()))
+                        .and_then(|active| title(conn, &atoms, active))
+                });
+                return Ok(Some(SourceApp { id: class, title }));
+            }
+        }
+
+        // Last resort: the process behind the owner window
+        let pid = window_property(conn, owner, atoms.net_wm_pid, AtomEnum::CARDINAL.into());
+        let name = pid.and_then(|pid| std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok());
+        Ok(name.map(|name| SourceApp { id: name.trim().to_string(), title: None }))
+    }
+
+    /// First 32-bit value of a property
+    fn window_property(conn: &RustConnection, window: Window, property: Atom, type_: Atom) -> Option<u32> {
+        let reply = conn.get_property(false, window, property, type_, 0, 1).ok()?.reply().ok()?;
+        let value = reply.value32()?.next();
+        value.filter(|&value| value != 0)
+    }
+
+    fn string_property(conn: &RustConnection, window: Window, property: Atom, type_: Atom) -> Option<Vec<u8>> {
+        let reply = conn.get_property(false, window, property, type_, 0, 1024).ok()?.reply().ok()?;
+        (!reply.value.is_empty()).then_some(reply.value)
+    }
+
+    /// The class part of WM_CLASS ("instance\0class\0")
+    fn wm_class(conn: &RustConnection, window: Window) -> Option<String> {
+        let value = string_property(conn, window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into())?;
+        let mut parts = value.split(|&b| b == 0).filter(|part| !part.is_empty());
+        let instance = parts.next()?;
+        let class = parts.next().unwrap_or(instance);
+        Some(String::from_utf8_lossy(class).into_owned())
+    }
+
+    fn title(conn: &RustConnection, atoms: &Atoms, window: Window) -> Option<String> {
+        let value = string_property(conn, window, atoms.net_wm_name, atoms.utf8_string)
+            .or_else(|| string_property(conn, window, AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()))?;
+        Some(String::from_utf8_lossy(&value).into_owned())
+    }
+}
@@ -0,0 +1,317 @@
+use crate::classify::TextKind;
+use crate::models::{ClipboardContent, ClipboardItem, Selection};
+use std::cmp::Ordering;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KindFilter {
+    Text,
+    Image,
+    Files,
+    // Detected kind of a text item (is:url, is:color...)
+    Detected(TextKind),
+}
+
+/// Parsed search entry text: free text plus `is:` and `app:` filters
+#[derive(Clone, Debug, Default)]
+pub struct SearchQuery {
+    terms: Vec<String>,
+    kind: Option<KindFilter>,
+    source: Option<Selection>,
+    // Part of the application name or window title an item was copied from
+    app: Option<String>,
+    pinned_only: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchRank {
+    Substring,
+    Fuzzy,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Self {
+        let mut query = SearchQuery::default();
+
+        for token in input.split_whitespace() {
+            match token.to_lowercase().as_str() {
+                "is:text" => query.kind = Some(KindFilter::Text),
+                "is:image" => query.kind = Some(KindFilter::Image),
+                "is:file" | "is:files" => query.kind = Some(KindFilter::Files),
+                "is:pinned" => query.pinned_only = true,
+                "is:primary" => query.source = Some(Selection::Primary),
+                "is:clipboard" => query.source = Some(Selection::Clipboard),
+                term if term.len() > 4 && term.starts_with("app:") => query.app = Some(term[4..].to_string()),
+                term => match term.strip_prefix("is:").and_then(TextKind::from_name) {
+                    Some(kind) => query.kind = Some(KindFilter::Detected(kind)),
+                    None => query.terms.push(term.to_string()),
+                },
+            }
+        }
+
+        query
+    }
+
+    /// Restrict to one selection unless the query asks for one explicitly
+    pub fn with_default_source(mut self, source: Selection) -> Self {
+        self.source.get_or_insert(source);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.kind.is_none() && self.source.is_none() && self.app.is_none() && !self.pinned_only
+    }
+
+    /// Results are ordered by how well they match, not by history order
+    pub fn is_ranked(&self) -> bool {
+        !self.terms.is_empty()
+    }
+
+    /// Whether `item` shows up in the results at all
+    pub fn matches(&self, item: &ClipboardItem) -> bool {
+        self.rank(item).is_some()
+    }
+
+    /// Result order of two matching items, substring hits before fuzzy hits
+    pub fn compare(&self, a: &ClipboardItem, b: &ClipboardItem) -> Ordering {
+        self.rank(a).cmp(&self.rank(b))
+    }
+
+    fn rank(&self, item: &ClipboardItem) -> Option<MatchRank> {
+        if self.pinned_only && !item.pinned {
+            return None;
+        }
+
+        if self.source.is_some_and(|source| source != item.source) {
+            return None;
+        }
+
+        if let Some(app) = &self.app {
+            let Some(source_app) = &item.source_app else {
+                return None;
+            };
+            let title_matches = source_app.title.as_ref().is_some_and(|title| title.to_lowercase().contains(app));
+            if !source_app.id.to_lowercase().contains(app) && !title_matches {
+                return None;
+            }
+        }
+
+        // HTML and RTF count as text
+        let kind_matches = match (self.kind, &item.content) {
+            (None, _) => true,
+            (Some(KindFilter::Image), content) => matches!(content, ClipboardContent::Image { .. }),
+            (Some(KindFilter::Files), content) => matches!(content, ClipboardContent::Files(_)),
+            // Sensitive items don't give away what they look like
+            (Some(KindFilter::Detected(kind)), _) => item.kind == kind && !item.is_sensitive(),
+            (Some(KindFilter::Text), content) => matches!(
+                content,
+                ClipboardContent::Text(_) | ClipboardContent::Html { .. } | ClipboardContent::Rtf { .. }
+            ),
+        };
+        if !kind_matches {
+            return None;
+        }
+
+        if self.terms.is_empty() {
+            return Some(MatchRank::Substring);
+        }
+        // Typing parts of a secret must not confirm it's in the history
+        if item.is_sensitive() {
+            return None;
+        }
+
+        // Long text is only searched in the part kept in memory (`storage::RESIDENT_TEXT`)
+        let haystack = match &item.content {
+            // Images can only be found through their dimensions
+            ClipboardContent::Image { width, height, .. } => format!("{}x{} {}×{}", width, height, width, height),
+            content => content.plain_text().unwrap_or_default().to_lowercase(),
+        };
+
+        let mut rank = MatchRank::Substring;
+        for term in &self.terms {
+            if haystack.contains(term.as_str()) {
+                continue;
+            }
+            if fuzzy_match(&haystack, term) {
+                rank = MatchRank::Fuzzy;
+            } else {
+                return None;
+            }
+        }
+        Some(rank)
+    }
+}
+
+/// Items matching the query, substring hits before fuzzy hits, history order otherwise
+pub fn filter_items<'a>(items: &'a [ClipboardItem], query: &SearchQuery) -> Vec<&'a ClipboardItem> {
+    if query.is_empty() {
+        return items.iter().collect();
+    }
+
+    let mut matches: Vec<(MatchRank, &ClipboardItem)> = items.iter()
+        .filter_map(|item| query.rank(item).map(|rank| (rank, item)))
+        .collect();
+    // Stable sort keeps pinned-first/newest-first order within each rank
+    matches.sort_by_key(|(rank, _)| *rank);
+    matches.into_iter().map(|(_, item)| item).collect()
+}
+
+/// All characters of `needle` appear in `haystack` in order
+fn fuzzy_match(haystack: &str, needle: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|n| chars.any(|h| h == n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SourceApp;
+    use crate::storage::RESIDENT_TEXT;
+
+    fn text(text: &str) -> ClipboardItem {
+        ClipboardItem::new(ClipboardContent::Text(text.to_string()), Selection::Clipboard)
+    }
+
+    fn image(width: u32, height: u32) -> ClipboardItem {
+        let content = ClipboardContent::Image { png_data: vec![1], thumbnail_png: Vec::new(), width, height };
+        ClipboardItem::new(content, Selection::Clipboard)
+    }
+
+    fn texts<'a>(items: &[&'a ClipboardItem]) -> Vec<&'a str> {
+        items.iter()
+            .map(|item| match &item.content {
+                ClipboardContent::Text(text) => text.as_str(),
+                _ => "<other>",
+            })
+            .collect()
+    }
+
+    fn search<'a>(items: &'a [ClipboardItem], input: &str) -> Vec<&'a ClipboardItem> {
+        filter_items(items, &SearchQuery::parse(input))
+    }
+
+    #[test]
+    fn empty_query_keeps_everything_in_order() {
+        let items = vec![text("b"), text("a")];
+        assert!(SearchQuery::parse("   ").is_empty());
+        assert_eq!(texts(&search(&items, "")), ["b", "a"]);
+    }
+
+    #[test]
+    fn substring_match_ignores_case() {
+        let items = vec![text("Hello World"), text("goodbye")];
+        assert_eq!(texts(&search(&items, "WORLD")), ["Hello World"]);
+        assert!(search(&items, "planet").is_empty());
+    }
+
+    #[test]
+    fn every_term_has_to_match() {
+        let items = vec![text("cargo build --release"), text("cargo test")];
+        assert_eq!(texts(&search(&items, "cargo release")), ["cargo build --release"]);
+    }
+
+    #[test]
+    fn fuzzy_match_needs_characters_in_order() {
+        assert!(fuzzy_match("clipboard manager", "cbm"));
+        assert!(!fuzzy_match("clipboard manager", "mbc"));
+        let items = vec![text("clipboard manager")];
+        assert_eq!(search(&items, "clpmgr").len(), 1);
+    }
+
+    #[test]
+    fn substring_hits_come_before_fuzzy_hits() {
+        // The first result is what the popup selects
+        let items = vec![text("a_b_c"), text("abc"), text("xyz")];
+        assert_eq!(texts(&search(&items, "abc")), ["abc", "a_b_c"]);
+    }
+
+    #[test]
+    fn ranking_wins_over_day() {
+        let mut older = text("abc");
+        older.timestamp -= chrono::Duration::days(2);
+        let items = vec![text("a_b_c"), older];
+
+        let query = SearchQuery::parse("abc");
+        assert!(query.is_ranked());
+        assert_eq!(texts(&filter_items(&items, &query)), ["abc", "a_b_c"]);
+        // Filters alone keep the history order, and with it the day headers
+        assert!(!SearchQuery::parse("is:text is:pinned").is_ranked());
+        assert!(!SearchQuery::parse("").is_ranked());
+    }
+
+    #[test]
+    fn long_text_is_searched_in_its_resident_part() {
+        let mut long = text(&format!("start {} end", "x".repeat(RESIDENT_TEXT)));
+        long.unload(RESIDENT_TEXT);
+        assert!(long.truncated);
+        let items = vec![long];
+        assert_eq!(search(&items, "start").len(), 1);
+        assert!(search(&items, "end").is_empty());
+    }
+
+    #[test]
+    fn kind_filters() {
+        let files = ClipboardItem::new(ClipboardContent::Files(vec!["file:///tmp/a".into()]), Selection::Clipboard);
+        let html = ClipboardItem::new(
+            ClipboardContent::Html { html: "<b>x</b>".into(), text: "x".into() },
+            Selection::Clipboard,
+        );
+        let items = vec![text("plain"), image(10, 20), files, html];
+
+        assert_eq!(search(&items, "is:text").len(), 2);
+        assert!(search(&items, "is:image")[0].is_image());
+        assert!(matches!(search(&items, "is:files")[0].content, ClipboardContent::Files(_)));
+        // Images are found through their dimensions
+        assert_eq!(search(&items, "10x20").len(), 1);
+    }
+
+    #[test]
+    fn detected_kind_filter() {
+        let items = vec![text("https://example.com"), text("just words")];
+        assert_eq!(texts(&search(&items, "is:url")), ["https://example.com"]);
+        assert_eq!(texts(&search(&items, "is:link")), ["https://example.com"]);
+    }
+
+    #[test]
+    fn sensitive_items_are_not_found_by_their_text() {
+        let mut secret = text("hunter2");
+        secret.expires_at = Some(std::time::Instant::now() + std::time::Duration::from_secs(60));
+        let items = vec![secret, text("hunter")];
+
+        assert_eq!(texts(&search(&items, "hunter")), ["hunter"]);
+        assert_eq!(texts(&search(&items, "htr2")), Vec::<&str>::new());
+        // Still listed when nothing is typed
+        assert_eq!(search(&items, "is:text").len(), 2);
+    }
+
+    #[test]
+    fn pinned_filter() {
+        let mut pinned = text("kept");
+        pinned.pinned = true;
+        let items = vec![pinned, text("other")];
+        assert_eq!(texts(&search(&items, "is:pinned")), ["kept"]);
+    }
+
+    #[test]
+    fn source_filter_and_default() {
+        let primary = ClipboardItem::new(ClipboardContent::Text("selected".into()), Selection::Primary);
+        let items = vec![primary, text("copied")];
+
+        assert_eq!(texts(&search(&items, "is:primary")), ["selected"]);
+        let query = SearchQuery::parse("").with_default_source(Selection::Clipboard);
+        assert_eq!(texts(&filter_items(&items, &query)), ["copied"]);
+        // Asking explicitly wins over the default
+        let query = SearchQuery::parse("is:primary").with_default_source(Selection::Clipboard);
+        assert_eq!(texts(&filter_items(&items, &query)), ["selected"]);
+    }
+
+    #[test]
+    fn app_filter_matches_id_or_title() {
+        let mut from_browser = text("a");
+        from_browser.source_app = Some(SourceApp { id: "Firefox".into(), title: Some("Docs — Mozilla".into()) });
+        let items = vec![from_browser, text("b")];
+
+        assert_eq!(texts(&search(&items, "app:firefox")), ["a"]);
+        assert_eq!(texts(&search(&items, "app:mozilla")), ["a"]);
+        assert!(search(&items, "app:terminal").is_empty());
+    }
+}
@@ -0,0 +1,485 @@
+// SQLite schema and row conversion. Item rows hold everything but the full
+// size image, which lives in a content-addressed blob table and is only read
+// when an image is pasted or exported.
+
+use super::format::legacy_timestamps;
+use crate::crypto::Cipher;
+use crate::models::{ClipboardContent, ClipboardItem, Selection};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Bumped whenever the tables or the encoding of a column change.
+/// Schema 2: timestamps are RFC 3339 in UTC instead of a wall clock time.
+/// Schema 3: items record the application they were copied from.
+/// The content column is bincode of StoredContent in every schema so far.
+const SCHEMA_VERSION: i32 = 3;
+
+pub enum OpenError {
+    // Written by a newer build, must not be touched
+    Newer(i32),
+    // Not a database or damaged
+    Damaged(String),
+}
+
+/// Open the database, creating or upgrading the schema as needed
+pub fn open(path: &Path) -> Result<Connection, OpenError> {
+    let damaged = |e: rusqlite::Error| OpenError::Damaged(e.to_string());
+    let conn = Connection::open(path).map_err(damaged)?;
+    conn.pragma_update(None, "journal_mode", "WAL").map_err(damaged)?;
+    conn.pragma_update(None, "foreign_keys", true).map_err(damaged)?;
+    conn.busy_timeout(std::time::Duration::from_secs(5)).map_err(damaged)?;
+    migrate(&conn)?;
+    Ok(conn)
+}
+
+fn migrate(conn: &Connection) -> Result<(), OpenError> {
+    let damaged = |e: rusqlite::Error| OpenError::Damaged(e.to_string());
+    let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).map_err(damaged)?;
+    match version {
+        0 => {
+            conn.execute_batch(
+                "BEGIN;
+                 CREATE TABLE blobs (
+                     hash TEXT PRIMARY KEY,
+                     data BLOB NOT NULL
+                 );
+                 CREATE TABLE items (
+                     seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                     id TEXT NOT NULL UNIQUE,
+                     pinned INTEGER NOT NULL,
+                     timestamp TEXT NOT NULL,
+                     source INTEGER NOT NULL,
+                     content_hash INTEGER NOT NULL,
+                     content BLOB NOT NULL,
+                     blob_hash TEXT REFERENCES blobs(hash),
+                     source_app BLOB
+                 );
+                 CREATE INDEX items_blob_hash ON items(blob_hash);
+                 PRAGMA user_version = 3;
+                 COMMIT;",
+            ).map_err(damaged)?;
+            Ok(())
+        }
+        1 => {
+            date_timestamps(conn).map_err(damaged)?;
+            add_source_app(conn).map_err(damaged)
+        }
+        2 => add_source_app(conn).map_err(damaged),
+        SCHEMA_VERSION => Ok(()),
+        newer => Err(OpenError::Newer(newer)),
+    }
+}
+
+/// Schema 1 stored only the time of day, guess the dates from the insertion order
+fn date_timestamps(conn: &Connection) -> rusqlite::Result<()> {
+    let rows: Vec<(i64, String)> = conn
+        .prepare("SELECT seq, timestamp FROM items ORDER BY seq DESC")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    let timestamps = legacy_timestamps(rows.iter().map(|(_, time)| (false, time.as_str())));
+
+    conn.execute_batch("BEGIN")?;
+    for ((seq, _), timestamp) in rows.iter().zip(timestamps) {
+        conn.execute("UPDATE items SET timestamp = ?2 WHERE seq = ?1", params![seq, timestamp_column(&timestamp)])?;
+    }
+    conn.execute_batch("PRAGMA user_version = 2; COMMIT;")
+}
+
+/// Schema 3 adds the source application, unknown for older items
+fn add_source_app(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "BEGIN;
+         ALTER TABLE items ADD COLUMN source_app BLOB;
+         PRAGMA user_version = 3;
+         COMMIT;",
+    )
+}
+
+/// Items loaded from the database
+pub struct Loaded {
+    pub items: Vec<ClipboardItem>,
+    // Rows that couldn't be decoded, they stay in the database untouched
+    pub unreadable: usize,
+}
+
+/// All items, pinned first and newest first. Images come without their full size
+/// data and text is cut at `max_text` bytes, see ClipboardItem::unload. Rows that
+/// can't be decoded are left out, Err only if the key is wrong.
+pub fn load_items(conn: &Connection, cipher: &mut Option<Cipher>, max_text: usize) -> Result<Loaded, String> {
+    let mut stmt = conn.prepare(
+        "SELECT id, pinned, timestamp, source, content_hash, content,
+                (SELECT length(data) FROM blobs WHERE hash = items.blob_hash), source_app
+         FROM items ORDER BY pinned DESC, seq DESC",
+    ).map_err(err)?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, bool>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, i64>(3)?,
+            row.get::<_, i64>(4)?,
+            row.get::<_, Vec<u8>>(5)?,
+            row.get::<_, Option<i64>>(6)?,
+            row.get::<_, Option<Vec<u8>>>(7)?,
+        ))
+    }).map_err(err)?;
+
+    let mut previous = None;
+    let mut items = Vec::new();
+    let mut unreadable = 0;
+    for row in rows {
+        let (id, pinned, timestamp, source, content_hash, content, blob_size, source_app) = row.map_err(err)?;
+        let content = match open_column(cipher, &mut previous, content) {
+            Ok(content) => content,
+            Err(e) => return Err(format!("cannot decrypt item {}: {}", id, e)),
+        };
+        let content = match bincode::deserialize::<StoredContent>(&content) {
+            Ok(content) => ClipboardContent::from(content),
+            Err(e) => {
+                eprintln!("Leaving out unreadable item {}: {}", id, e);
+                unreadable += 1;
+                continue;
+            }
+        };
+
+        let mut item = ClipboardItem::new(content, source_from_column(source));
+        item.id = id;
+        item.pinned = pinned;
+        item.timestamp = timestamp_from_column(&timestamp);
+        // Image hashes cover the full PNG, which isn't loaded
+        item.content_hash = content_hash as u64;
+        item.size += blob_size.unwrap_or(0) as usize;
+        // Window titles can be telling, so the column is encrypted like the content
+        item.source_app = source_app
+            .and_then(|data| open_column(cipher, &mut previous, data).ok())
+            .and_then(|data| bincode::deserialize(&data).ok());
+        item.unload(max_text);
+        items.push(item);
+    }
+    Ok(Loaded { items, unreadable })
+}
+
+/// Whether the stored rows are encrypted, None for an empty database
+pub fn is_encrypted(conn: &Connection) -> Result<Option<bool>, String> {
+    let content: Option<Vec<u8>> = conn
+        .query_row("SELECT content FROM items LIMIT 1", [], |row| row.get(0))
+        .optional()
+        .map_err(err)?;
+    Ok(content.map(|content| Cipher::is_sealed(&content)))
+}
+
+/// Full size PNG of an image item
+pub fn load_image(conn: &Connection, cipher: &mut Option<Cipher>, id: &str) -> Option<Vec<u8>> {
+    let data: Vec<u8> = conn.query_row(
+        "SELECT blobs.data FROM items JOIN blobs ON blobs.hash = items.blob_hash WHERE items.id = ?1",
+        params![id],
+        |row| row.get(0),
+    ).ok()?;
+    open_column(cipher, &mut None, data).ok()
+}
+
+/// Full content of an item, including the image
+pub fn load_content(conn: &Connection, cipher: &mut Option<Cipher>, id: &str) -> Option<ClipboardContent> {
+    let data: Vec<u8> = conn.query_row("SELECT content FROM items WHERE id = ?1", params![id], |row| row.get(0)).ok()?;
+    let data = open_column(cipher, &mut None, data).ok()?;
+    let mut content = ClipboardContent::from(bincode::deserialize::<StoredContent>(&data).ok()?);
+    if let ClipboardContent::Image { png_data, .. } = &mut content {
+        *png_data = load_image(conn, cipher, id)?;
+    }
+    Some(content)
+}
+
+/// Rewrite every row with the current key, or unencrypted without one. Rows
+/// are read one at a time, and those that can't be opened are left as they are.
+pub fn reseal(tx: &Transaction, cipher: &mut Option<Cipher>) -> Result<(), String> {
+    let mut previous = None;
+    reseal_column(tx, cipher, &mut previous, "items", "id", "content")?;
+    reseal_column(tx, cipher, &mut previous, "items", "id", "source_app")?;
+    reseal_column(tx, cipher, &mut previous, "blobs", "hash", "data")
+}
+
+fn reseal_column(
+    tx: &Transaction,
+    cipher: &mut Option<Cipher>,
+    previous: &mut Option<Cipher>,
+    table: &str,
+    key: &str,
+    column: &str,
+) -> Result<(), String> {
+    let keys: Vec<String> = tx
+        .prepare(&format!("SELECT {} FROM {} WHERE {} IS NOT NULL", key, table, column))
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+        .map_err(err)?;
+
+    for value in keys {
+        let data: Vec<u8> = tx
+            .query_row(&format!("SELECT {} FROM {} WHERE {} = ?1", column, table, key), params![value], |row| row.get(0))
+            .map_err(err)?;
+        let data = match open_column(cipher, previous, data) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Leaving {} {} as it is: {}", table, value, e);
+                continue;
+            }
+        };
+        tx.execute(
+            &format!("UPDATE {} SET {} = ?2 WHERE {} = ?1", table, column, key),
+            params![value, seal_column(cipher, data)?],
+        ).map_err(err)?;
+    }
+    Ok(())
+}
+
+/// Insert an item, or update it in place (keeping its position) if it exists
+pub fn upsert(tx: &Transaction, cipher: &Option<Cipher>, item: &ClipboardItem) -> Result<(), String> {
+    let mut content = StoredContent::from(item.content.clone());
+    let mut blob_hash = None;
+
+    if let StoredContent::Image { png_data, .. } = &mut content {
+        // Rewriting an item loaded without its image keeps the stored blob
+        if png_data.is_empty() {
+            blob_hash = tx.query_row("SELECT blob_hash FROM items WHERE id = ?1", params![item.id], |row| row.get(0))
+                .optional()
+                .map_err(err)?
+                .flatten();
+        } else {
+            let data = std::mem::take(png_data);
+            let hash = format!("{:x}", Sha256::digest(&data));
+            tx.execute(
+                // Updating also re-encrypts blobs when encryption is switched on or off
+                "INSERT INTO blobs (hash, data) VALUES (?1, ?2)
+                 ON CONFLICT(hash) DO UPDATE SET data = excluded.data",
+                params![hash, seal_column(cipher, data)?],
+            ).map_err(err)?;
+            blob_hash = Some(hash);
+        }
+    }
+
+    let content = bincode::serialize(&content).map_err(err)?;
+    let source_app = match &item.source_app {
+        Some(source_app) => Some(seal_column(cipher, bincode::serialize(source_app).map_err(err)?)?),
+        None => None,
+    };
+    tx.execute(
+        "INSERT INTO items (id, pinned, timestamp, source, content_hash, content, blob_hash, source_app)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(id) DO UPDATE SET
+             pinned = excluded.pinned,
+             timestamp = excluded.timestamp,
+             source = excluded.source,
+             content_hash = excluded.content_hash,
+             content = excluded.content,
+             blob_hash = excluded.blob_hash,
+             source_app = excluded.source_app",
+        params![
+            item.id,
+            item.pinned,
+            timestamp_column(&item.timestamp),
+            source_column(item.source),
+            item.content_hash as i64,
+            seal_column(cipher, content)?,
+            blob_hash,
+            source_app,
+        ],
+    ).map_err(err)?;
+    Ok(())
+}
+
+pub fn set_pinned(tx: &Transaction, id: &str, pinned: bool) -> Result<(), String> {
+    tx.execute("UPDATE items SET pinned = ?2 WHERE id = ?1", params![id, pinned]).map_err(err)?;
+    Ok(())
+}
+
+pub fn delete(tx: &Transaction, ids: &[String]) -> Result<(), String> {
+    for id in ids {
+        tx.execute("DELETE FROM items WHERE id = ?1", params![id]).map_err(err)?;
+    }
+    // Blobs are shared between items with the same image
+    tx.execute(
+        "DELETE FROM blobs WHERE hash NOT IN (SELECT blob_hash FROM items WHERE blob_hash IS NOT NULL)",
+        [],
+    ).map_err(err)?;
+    Ok(())
+}
+
+/// Frozen copy of ClipboardContent as the content column stores it. Changes
+/// to ClipboardContent don't change the column, a new layout needs a schema
+/// version and a migration.
+#[derive(Serialize, Deserialize)]
+enum StoredContent {
+    Text(String),
+    Image {
+        // Always empty, the data is in the blobs table
+        png_data: Vec<u8>,
+        thumbnail_png: Vec<u8>,
+        width: u32,
+        height: u32,
+    },
+    Html {
+        html: String,
+        text: String,
+    },
+    Rtf {
+        rtf: String,
+        text: String,
+    },
+    Files(Vec<String>),
+}
+
+impl From<ClipboardContent> for StoredContent {
+    fn from(content: ClipboardContent) -> Self {
+        match content {
+            ClipboardContent::Text(text) => StoredContent::Text(text),
+            ClipboardContent::Image { png_data, thumbnail_png, width, height } => {
+                StoredContent::Image { png_data, thumbnail_png, width, height }
+            }
+            ClipboardContent::Html { html, text } => StoredContent::Html { html, text },
+            ClipboardContent::Rtf { rtf, text } => StoredContent::Rtf { rtf, text },
+            ClipboardContent::Files(uris) => StoredContent::Files(uris),
+        }
+    }
+}
+
+impl From<StoredContent> for ClipboardContent {
+    fn from(content: StoredContent) -> Self {
+        match content {
+            StoredContent::Text(text) => ClipboardContent::Text(text),
+            StoredContent::Image { png_data, thumbnail_png, width, height } => {
+                ClipboardContent::Image { png_data, thumbnail_png, width, height }
+            }
+            StoredContent::Html { html, text } => ClipboardContent::Html { html, text },
+            StoredContent::Rtf { rtf, text } => ClipboardContent::Rtf { rtf, text },
+            StoredContent::Files(uris) => ClipboardContent::Files(uris),
+        }
+    }
+}
+
+fn seal_column(cipher: &Option<Cipher>, data: Vec<u8>) -> Result<Vec<u8>, String> {
+    match cipher {
+        Some(cipher) => cipher.seal(&data),
+        None => Ok(data),
+    }
+}
+
+/// `previous` caches the key of rows written while encryption was on, if it's off now
+fn open_column(cipher: &mut Option<Cipher>, previous: &mut Option<Cipher>, data: Vec<u8>) -> Result<Vec<u8>, String> {
+    if !Cipher::is_sealed(&data) {
+        return Ok(data);
+    }
+    if let Some(cipher) = cipher {
+        return cipher.open(&data);
+    }
+    if previous.is_none() {
+        *previous = Some(Cipher::for_sealed(&data)?);
+    }
+    previous.as_mut().unwrap().open(&data)
+}
+
+// Sorts in time order as text
+fn timestamp_column(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn timestamp_from_column(timestamp: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+fn source_column(source: Selection) -> i64 {
+    match source {
+        Selection::Clipboard => 0,
+        Selection::Primary => 1,
+    }
+}
+
+fn source_from_column(source: i64) -> Selection {
+    match source {
+        1 => Selection::Primary,
+        _ => Selection::Clipboard,
+    }
+}
+
+fn err(e: impl std::fmt::Display) -> String {
+    e.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).ok().unwrap();
+        conn
+    }
+
+    fn insert(conn: &mut Connection, item: &ClipboardItem) {
+        let tx = conn.transaction().unwrap();
+        upsert(&tx, &None, item).unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn items_round_trip() {
+        let mut conn = database();
+        let html = ClipboardContent::Html { html: "<b>hi</b>".into(), text: "hi".into() };
+        let image = ClipboardContent::Image { png_data: vec![1, 2, 3], thumbnail_png: vec![4], width: 5, height: 6 };
+        let mut pinned = ClipboardItem::new(html.clone(), Selection::Primary);
+        pinned.pinned = true;
+        insert(&mut conn, &pinned);
+        insert(&mut conn, &ClipboardItem::new(image, Selection::Clipboard));
+
+        let loaded = load_items(&conn, &mut None, usize::MAX).unwrap();
+        assert_eq!(loaded.unreadable, 0);
+        assert_eq!(loaded.items[0].id, pinned.id);
+        assert_eq!(loaded.items[0].content, html);
+        assert_eq!(loaded.items[0].source, Selection::Primary);
+        // The full image stays in the blobs table until asked for
+        let ClipboardContent::Image { png_data, thumbnail_png, .. } = &loaded.items[1].content else {
+            panic!("not an image");
+        };
+        assert!(png_data.is_empty());
+        assert_eq!(thumbnail_png, &[4]);
+        assert_eq!(loaded.items[1].size, 3);
+        assert_eq!(load_image(&conn, &mut None, &loaded.items[1].id), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn long_text_is_read_back_when_needed() {
+        let mut conn = database();
+        let text = "é".repeat(100);
+        let item = ClipboardItem::new(ClipboardContent::Text(text.clone()), Selection::Clipboard);
+        insert(&mut conn, &item);
+
+        let loaded = load_items(&conn, &mut None, 11).unwrap();
+        let excerpt = &loaded.items[0];
+        assert!(excerpt.truncated);
+        // Cut at a character boundary
+        assert_eq!(excerpt.content, ClipboardContent::Text("é".repeat(5)));
+        assert_eq!(excerpt.size, text.len());
+        assert_eq!(excerpt.content_hash, item.content_hash);
+        assert_eq!(load_content(&conn, &mut None, &item.id), Some(ClipboardContent::Text(text)));
+    }
+
+    #[test]
+    fn unreadable_rows_are_counted_and_kept() {
+        let mut conn = database();
+        insert(&mut conn, &ClipboardItem::new(ClipboardContent::Text("kept".into()), Selection::Clipboard));
+        conn.execute(
+            "INSERT INTO items (id, pinned, timestamp, source, content_hash, content)
+             VALUES ('broken', 0, '2024-01-01T00:00:00.000Z', 0, 0, x'ff')",
+            [],
+        ).unwrap();
+
+        let loaded = load_items(&conn, &mut None, usize::MAX).unwrap();
+        assert_eq!(loaded.unreadable, 1);
+        assert_eq!(loaded.items.len(), 1);
+        assert_eq!(loaded.items[0].content, ClipboardContent::Text("kept".into()));
+        let rows: i64 = conn.query_row("SELECT count(*) FROM items", [], |row| row.get(0)).unwrap();
+        assert_eq!(rows, 2);
+    }
+}
@@ -0,0 +1,414 @@
+// Layout of the old bincode history file: an 8 byte magic, a little-endian u32
+// schema version, then the payload (possibly sealed by crate::crypto). Files
+// written before the header existed start directly with the payload.
+
+use crate::models::ClipboardItem;
+use bincode::Options;
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone, Utc};
+
+const MAGIC: &[u8; 8] = b"CMGRHIST";
+const HEADER_LEN: usize = MAGIC.len() + 4;
+
+/// Schema 1: text and images only, no source selection (always headerless).
+/// Schema 2: HTML/RTF/file lists and the source selection.
+/// Builds in between wrote the schema 2 content without the source selection,
+/// also headerless.
+pub const CURRENT_VERSION: u32 = 2;
+
+// Headerless files report this version
+const UNVERSIONED: u32 = 0;
+
+// Same encoding as bincode::deserialize, but trailing bytes mean we guessed the wrong schema
+fn strict() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+}
+
+/// Schema version and payload of a history file
+pub fn split_header(data: &[u8]) -> Result<(u32, &[u8]), String> {
+    if !data.starts_with(MAGIC) {
+        return Ok((UNVERSIONED, data));
+    }
+    if data.len() < HEADER_LEN {
+        return Err("truncated header".to_string());
+    }
+    let version = u32::from_le_bytes(data[MAGIC.len()..HEADER_LEN].try_into().unwrap());
+    Ok((version, &data[HEADER_LEN..]))
+}
+
+/// Decode a payload of the given schema version into current items.
+/// The format is frozen, each schema has its own copy of the types below.
+pub fn decode_items(version: u32, payload: &[u8]) -> Result<Vec<ClipboardItem>, String> {
+    match version {
+        CURRENT_VERSION => v2::decode(payload),
+        1 => v1::decode(payload),
+        // Older builds wrote schema 1 or 2 without a header. Strict decoding
+        // fails on the wrong layout, the first one to fit is it.
+        UNVERSIONED => v1::decode(payload)
+            .or_else(|_| v2::decode_without_source(payload))
+            .or_else(|_| v2::decode(payload)),
+        newer => Err(format!("schema version {} is newer than this build supports", newer)),
+    }
+}
+
+/// Dates for the wall clock times ("%H:%M:%S") older versions stored, given
+/// newest first as (pinned, time). Best effort: a time later on the clock than
+/// the newer item before it is taken to be from the day before. Pinned items
+/// were moved to the top, so they form a chain of their own.
+pub fn legacy_timestamps<'a>(clock_times: impl IntoIterator<Item = (bool, &'a str)>) -> Vec<DateTime<Utc>> {
+    let now = Local::now();
+    // (day, time) of the previous item in each chain
+    let mut chains = [(now.date_naive(), now.time()); 2];
+
+    clock_times.into_iter()
+        .map(|(pinned, clock_time)| {
+            let (day, newer) = &mut chains[pinned as usize];
+            let time = NaiveTime::parse_from_str(clock_time, "%H:%M:%S").unwrap_or(*newer);
+            if time > *newer {
+                *day -= Duration::days(1);
+            }
+            *newer = time;
+            Local.from_local_datetime(&day.and_time(time))
+                .earliest()
+                .unwrap_or(now)
+                .with_timezone(&Utc)
+        })
+        .collect()
+}
+
+mod v1 {
+    use super::{legacy_timestamps, strict};
+    use crate::models::{ClipboardContent, ClipboardItem, Selection};
+    use bincode::Options;
+    use chrono::{DateTime, Utc};
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    enum ContentV1 {
+        Text(String),
+        Image {
+            png_data: Vec<u8>,
+            thumbnail_png: Vec<u8>,
+            width: u32,
+            height: u32,
+        },
+    }
+
+    #[derive(Deserialize)]
+    struct ItemV1 {
+        content: ContentV1,
+        timestamp: String,
+        pinned: bool,
+        id: String,
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Vec<ClipboardItem>, String> {
+        let items: Vec<ItemV1> = strict().deserialize(payload).map_err(|e| e.to_string())?;
+        let timestamps = legacy_timestamps(items.iter().map(|item| (item.pinned, item.timestamp.as_str())));
+        Ok(items.into_iter().zip(timestamps).map(|(item, timestamp)| migrate(item, timestamp)).collect())
+    }
+
+    fn migrate(item: ItemV1, timestamp: DateTime<Utc>) -> ClipboardItem {
+        let content = match item.content {
+            ContentV1::Text(text) => ClipboardContent::Text(text),
+            ContentV1::Image { png_data, thumbnail_png, width, height } => {
+                ClipboardContent::Image { png_data, thumbnail_png, width, height }
+            }
+        };
+        let mut migrated = ClipboardItem::new(content, Selection::Clipboard);
+        migrated.timestamp = timestamp;
+        migrated.pinned = item.pinned;
+        migrated.id = item.id;
+        migrated
+    }
+}
+
+mod v2 {
+    use super::{legacy_timestamps, strict};
+    use crate::models::{ClipboardContent, ClipboardItem, Selection};
+    use bincode::Options;
+    use chrono::{DateTime, Utc};
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    enum ContentV2 {
+        Text(String),
+        Image {
+            png_data: Vec<u8>,
+            thumbnail_png: Vec<u8>,
+            width: u32,
+            height: u32,
+        },
+        Html {
+            html: String,
+            text: String,
+        },
+        Rtf {
+            rtf: String,
+            text: String,
+        },
+        Files(Vec<String>),
+    }
+
+    #[derive(Deserialize)]
+    enum SelectionV2 {
+        Clipboard,
+        Primary,
+    }
+
+    #[derive(Deserialize)]
+    struct ItemV2 {
+        content: ContentV2,
+        timestamp: String,
+        pinned: bool,
+        id: String,
+        source: SelectionV2,
+    }
+
+    // Rich formats were captured before items recorded their selection
+    #[derive(Deserialize)]
+    struct ItemWithoutSource {
+        content: ContentV2,
+        timestamp: String,
+        pinned: bool,
+        id: String,
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Vec<ClipboardItem>, String> {
+        let items: Vec<ItemV2> = strict().deserialize(payload).map_err(|e| e.to_string())?;
+        Ok(migrate_all(items))
+    }
+
+    pub fn decode_without_source(payload: &[u8]) -> Result<Vec<ClipboardItem>, String> {
+        let items: Vec<ItemWithoutSource> = strict().deserialize(payload).map_err(|e| e.to_string())?;
+        Ok(migrate_all(items.into_iter().map(|item| ItemV2 {
+            content: item.content,
+            timestamp: item.timestamp,
+            pinned: item.pinned,
+            id: item.id,
+            source: SelectionV2::Clipboard,
+        }).collect()))
+    }
+
+    fn migrate_all(items: Vec<ItemV2>) -> Vec<ClipboardItem> {
+        let timestamps = legacy_timestamps(items.iter().map(|item| (item.pinned, item.timestamp.as_str())));
+        items.into_iter().zip(timestamps).map(|(item, timestamp)| migrate(item, timestamp)).collect()
+    }
+
+    fn migrate(item: ItemV2, timestamp: DateTime<Utc>) -> ClipboardItem {
+        let content = match item.content {
+            ContentV2::Text(text) => ClipboardContent::Text(text),
+            ContentV2::Image { png_data, thumbnail_png, width, height } => {
+                ClipboardContent::Image { png_data, thumbnail_png, width, height }
+            }
+            ContentV2::Html { html, text } => ClipboardContent::Html { html, text },
+            ContentV2::Rtf { rtf, text } => ClipboardContent::Rtf { rtf, text },
+            ContentV2::Files(uris) => ClipboardContent::Files(uris),
+        };
+        let source = match item.source {
+            SelectionV2::Clipboard => Selection::Clipboard,
+            SelectionV2::Primary => Selection::Primary,
+        };
+        let mut migrated = ClipboardItem::new(content, source);
+        migrated.timestamp = timestamp;
+        migrated.pinned = item.pinned;
+        migrated.id = item.id;
+        migrated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ClipboardContent, Selection};
+    use chrono::{Local, NaiveTime};
+    use serde::Serialize;
+
+    // Writers for the frozen layouts, as the old builds did it
+    #[derive(Serialize)]
+    enum ContentV1 {
+        Text(String),
+        Image { png_data: Vec<u8>, thumbnail_png: Vec<u8>, width: u32, height: u32 },
+    }
+
+    #[derive(Serialize)]
+    struct ItemV1 {
+        content: ContentV1,
+        timestamp: String,
+        pinned: bool,
+        id: String,
+    }
+
+    #[derive(Serialize)]
+    enum ContentV2 {
+        Text(String),
+        Image { png_data: Vec<u8>, thumbnail_png: Vec<u8>, width: u32, height: u32 },
+        Html { html: String, text: String },
+        Rtf { rtf: String, text: String },
+        Files(Vec<String>),
+    }
+
+    #[derive(Serialize)]
+    struct ItemWithoutSource {
+        content: ContentV2,
+        timestamp: String,
+        pinned: bool,
+        id: String,
+    }
+
+    #[derive(Serialize)]
+    enum SelectionV2 {
+        Clipboard,
+        Primary,
+    }
+
+    #[derive(Serialize)]
+    struct ItemV2 {
+        content: ContentV2,
+        timestamp: String,
+        pinned: bool,
+        id: String,
+        source: SelectionV2,
+    }
+
+    fn with_header(version: u32, payload: &[u8]) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&version.to_le_bytes());
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn decode_file(data: &[u8]) -> Result<Vec<ClipboardItem>, String> {
+        let (version, payload) = split_header(data)?;
+        decode_items(version, payload)
+    }
+
+    fn v1_payload() -> Vec<u8> {
+        bincode::serialize(&vec![
+            ItemV1 { content: ContentV1::Text("hello".into()), timestamp: "10:00:00".into(), pinned: true, id: "a".into() },
+            ItemV1 {
+                content: ContentV1::Image { png_data: vec![1, 2], thumbnail_png: vec![3], width: 4, height: 5 },
+                timestamp: "09:00:00".into(),
+                pinned: false,
+                id: "b".into(),
+            },
+        ]).unwrap()
+    }
+
+    #[test]
+    fn header_is_optional() {
+        assert_eq!(split_header(b"payload").unwrap(), (UNVERSIONED, &b"payload"[..]));
+        let data = with_header(7, b"payload");
+        assert_eq!(split_header(&data).unwrap(), (7, &b"payload"[..]));
+        assert!(split_header(&data[..HEADER_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn schema_1_migrates() {
+        for data in [v1_payload(), with_header(1, &v1_payload())] {
+            let items = decode_file(&data).unwrap();
+            assert_eq!(items.len(), 2);
+            assert_eq!(items[0].content, ClipboardContent::Text("hello".into()));
+            assert!(items[0].pinned);
+            assert_eq!(items[0].id, "a");
+            assert_eq!(items[0].source, Selection::Clipboard);
+            assert!(matches!(items[1].content, ClipboardContent::Image { width: 4, height: 5, .. }));
+            assert_eq!(items[1].id, "b");
+        }
+    }
+
+    #[test]
+    fn rich_content_without_source_migrates() {
+        let payload = bincode::serialize(&vec![
+            ItemWithoutSource {
+                content: ContentV2::Html { html: "<b>hi</b>".into(), text: "hi".into() },
+                timestamp: "10:00:00".into(),
+                pinned: false,
+                id: "a".into(),
+            },
+            ItemWithoutSource {
+                content: ContentV2::Text("plain".into()),
+                timestamp: "09:00:00".into(),
+                pinned: true,
+                id: "b".into(),
+            },
+            ItemWithoutSource {
+                content: ContentV2::Image { png_data: vec![1], thumbnail_png: vec![2], width: 3, height: 4 },
+                timestamp: "08:00:00".into(),
+                pinned: false,
+                id: "c".into(),
+            },
+        ]).unwrap();
+        let items = decode_file(&payload).unwrap();
+        assert_eq!(items[0].content, ClipboardContent::Html { html: "<b>hi</b>".into(), text: "hi".into() });
+        assert_eq!(items[1].content, ClipboardContent::Text("plain".into()));
+        assert!(items[1].pinned);
+        assert!(matches!(items[2].content, ClipboardContent::Image { width: 3, height: 4, .. }));
+        assert!(items.iter().all(|item| item.source == Selection::Clipboard));
+    }
+
+    #[test]
+    fn schema_2_migrates() {
+        let payload = bincode::serialize(&vec![
+            ItemV2 {
+                content: ContentV2::Files(vec!["file:///tmp/a".into()]),
+                timestamp: "10:00:00".into(),
+                pinned: false,
+                id: "a".into(),
+                source: SelectionV2::Clipboard,
+            },
+            ItemV2 {
+                content: ContentV2::Rtf { rtf: "{\\rtf1 hi}".into(), text: "hi".into() },
+                timestamp: "09:00:00".into(),
+                pinned: false,
+                id: "b".into(),
+                source: SelectionV2::Primary,
+            },
+        ]).unwrap();
+        for data in [payload.clone(), with_header(CURRENT_VERSION, &payload)] {
+            let items = decode_file(&data).unwrap();
+            assert_eq!(items[0].content, ClipboardContent::Files(vec!["file:///tmp/a".into()]));
+            assert_eq!(items[1].source, Selection::Primary);
+            assert_eq!(items[1].content.plain_text().as_deref(), Some("hi"));
+        }
+    }
+
+    #[test]
+    fn newer_and_damaged_files_are_refused() {
+        assert!(decode_file(&with_header(CURRENT_VERSION + 1, &v1_payload())).is_err());
+        let payload = v1_payload();
+        assert!(decode_file(&payload[..payload.len() - 3]).is_err());
+        // Strict decoding notices bytes the schema doesn't account for
+        let mut trailing = payload.clone();
+        trailing.push(0);
+        assert!(decode_file(&with_header(1, &trailing)).is_err());
+    }
+
+    #[test]
+    fn legacy_timestamps_walk_back_in_time() {
+        let timestamps = legacy_timestamps([(false, "10:00:00"), (false, "09:00:00"), (false, "23:00:00")]);
+        let local: Vec<_> = timestamps.iter().map(|t| t.with_timezone(&Local)).collect();
+
+        let time = |s| NaiveTime::parse_from_str(s, "%H:%M:%S").unwrap();
+        assert_eq!(local[1].time(), time("09:00:00"));
+        assert_eq!(local[2].time(), time("23:00:00"));
+        assert_eq!(local[0].date_naive(), local[1].date_naive());
+        // Later on the clock than the newer item: the day before
+        assert_eq!(local[2].date_naive().succ_opt(), Some(local[1].date_naive()));
+        assert!(local[0] > local[1] && local[1] > local[2]);
+    }
+
+    #[test]
+    fn pinned_items_have_their_own_chain() {
+        let timestamps = legacy_timestamps([(true, "00:00:00"), (false, "00:00:00")]);
+        // The pinned item being older doesn't move the unpinned one back a day
+        assert_eq!(timestamps[0], timestamps[1]);
+    }
+
+    #[test]
+    fn unreadable_times_take_the_newer_one() {
+        let timestamps = legacy_timestamps([(false, "08:00:00"), (false, "garbage")]);
+        assert_eq!(timestamps[0], timestamps[1]);
+    }
+}
@@ -0,0 +1,142 @@
+// The single-file bincode history used before the SQLite database. Only read
+// once, to import it into a freshly created database.
+
+use super::format;
+use crate::crypto::Cipher;
+use crate::models::ClipboardItem;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The old save thread kept clipboard_history.bin.1 (newest) .. .N next to it
+const SNAPSHOTS: usize = 3;
+
+pub enum LoadError {
+    // Damaged or truncated, worth trying an older snapshot
+    Unreadable(String),
+    // Failed to decrypt, either the wrong key or damaged ciphertext
+    Sealed(String),
+    // Readable in principle but not by us (newer schema)
+    Locked(String),
+}
+
+pub struct Imported {
+    pub items: Vec<ClipboardItem>,
+    // Set when the live file was damaged and a snapshot was used instead
+    pub recovered_from: Option<PathBuf>,
+}
+
+/// Read the old history, falling back to the newest snapshot that can still be read.
+/// Ok(None) when there is nothing to import.
+pub fn read_history(path: &Path, cipher: &mut Option<Cipher>) -> Result<Option<Imported>, String> {
+    // Set when the live file failed to decrypt. Only a snapshot opening with
+    // the same key tells a damaged file apart from a wrong key.
+    let mut sealed: Option<String> = None;
+
+    for (index, candidate) in history_files(path).into_iter().enumerate() {
+        if !candidate.exists() {
+            continue;
+        }
+        match read_file(&candidate, cipher) {
+            // A plain snapshot doesn't say anything about the key
+            Ok((_, false)) if sealed.is_some() => {}
+            Ok((items, _)) => {
+                if let Some(e) = sealed {
+                    backup_unreadable(path, &e);
+                }
+                return Ok(Some(Imported {
+                    items,
+                    recovered_from: (index > 0).then_some(candidate),
+                }));
+            }
+            Err(LoadError::Unreadable(e)) => {
+                if index == 0 {
+                    backup_unreadable(&candidate, &e);
+                } else {
+                    eprintln!("Skipping unreadable snapshot {}: {}", candidate.display(), e);
+                }
+            }
+            Err(LoadError::Sealed(e)) if index == 0 => sealed = Some(e),
+            // The live file is the one worth reporting
+            Err(LoadError::Sealed(_) | LoadError::Locked(_)) if sealed.is_some() => {}
+            // Snapshots were written the same way, no point in trying them
+            Err(LoadError::Sealed(e) | LoadError::Locked(e)) => {
+                return Err(format!("{}: {}", candidate.display(), e));
+            }
+        }
+    }
+
+    match sealed {
+        Some(e) => Err(format!("{}: {}", path.display(), e)),
+        None => Ok(None),
+    }
+}
+
+/// Keep the imported files around under another name, they're not read again
+pub fn mark_imported(path: &Path) {
+    for file in history_files(path) {
+        if let Some(name) = file.file_name() {
+            let mut imported = name.to_os_string();
+            imported.push(".imported");
+            let _ = fs::rename(&file, file.with_file_name(imported));
+        }
+    }
+}
+
+/// Whether there is an old history (or one of its snapshots) to import
+pub fn exists(path: &Path) -> bool {
+    history_files(path).iter().any(|file| file.exists())
+}
+
+/// The items in one file, and whether it was encrypted
+fn read_file(path: &Path, cipher: &mut Option<Cipher>) -> Result<(Vec<ClipboardItem>, bool), LoadError> {
+    let data = fs::read(path).map_err(|e| LoadError::Unreadable(e.to_string()))?;
+    let (version, payload) = format::split_header(&data).map_err(LoadError::Unreadable)?;
+
+    if version > format::CURRENT_VERSION {
+        return Err(LoadError::Locked("written by a newer version".to_string()));
+    }
+
+    let sealed = Cipher::is_sealed(payload);
+    let plaintext = if sealed {
+        let opened = match cipher.as_mut() {
+            Some(cipher) => cipher.open(payload),
+            // Encryption was turned off since
+            None => Cipher::for_sealed(payload).and_then(|mut cipher| cipher.open(payload)),
+        };
+        // Most likely the wrong key, a damaged file can't be told apart
+        opened.map_err(LoadError::Sealed)?
+    } else {
+        payload.to_vec()
+    };
+
+    let items = format::decode_items(version, &plaintext).map_err(LoadError::Unreadable)?;
+    Ok((items, sealed))
+}
+
+/// The live file followed by its snapshots, newest first
+fn history_files(path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![path.to_path_buf()];
+    files.extend((1..=SNAPSHOTS).map(|n| path.with_extension(format!("bin.{}", n))));
+    files
+}
+
+/// Move an unreadable file out of the way instead of overwriting it.
+/// Returns where it went.
+pub fn backup_unreadable(path: &Path, reason: &str) -> Option<PathBuf> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let backup = path.with_extension(format!(
+        "{}.corrupt-{}",
+        extension,
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+    ));
+    match fs::rename(path, &backup) {
+        Ok(()) => {
+            eprintln!("Could not read {} ({}), moved it to {}", path.display(), reason, backup.display());
+            Some(backup)
+        }
+        Err(e) => {
+            eprintln!("Could not read {} ({}) or back it up: {}", path.display(), reason, e);
+            None
+        }
+    }
+}
@@ -0,0 +1,292 @@
+mod db;
+mod format;
+mod legacy;
+
+use crate::config::EncryptionConfig;
+use crate::crypto::Cipher;
+use crate::models::{ClipboardContent, ClipboardItem};
+use parking_lot::Mutex;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+const DATABASE_FILE: &str = "clipboard_history.db";
+// Imported into the database on first start
+const LEGACY_FILE: &str = "clipboard_history.bin";
+
+// Text kept in memory per stored item, the rest is read back when needed.
+// Search only sees this much of each item.
+pub const RESIDENT_TEXT: usize = 16 * 1024;
+
+enum StorageOp {
+    Upsert(Arc<ClipboardItem>),
+    SetPinned { id: String, pinned: bool },
+    Delete(Vec<String>),
+    // Encryption was switched on or off since the rows were written
+    Reseal,
+}
+
+// Items queued for the writer, the only full copy until they're committed
+type Pending = Arc<Mutex<HashMap<String, Arc<ClipboardItem>>>>;
+
+pub struct Storage {
+    data_dir: PathBuf,
+    write_sender: mpsc::Sender<StorageOp>,
+    // Reads happen on the caller's thread, writes on the writer thread
+    reader: Option<Mutex<Connection>>,
+    // None when encryption is disabled
+    cipher: Arc<Mutex<Option<Cipher>>>,
+    // Set when the history can't be read back with the current key or
+    // schema, so it isn't overwritten
+    read_only: AtomicBool,
+    // Shown to the user once when the history had to be recovered
+    recovery_notice: Mutex<Option<String>>,
+    pending: Pending,
+}
+
+impl Storage {
+    pub fn new(encryption: &EncryptionConfig) -> Self {
+        let mut data_dir = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+        data_dir.push("clipboard_manager");
+        Self::with_dir(data_dir, encryption)
+    }
+
+    /// Keep the history in `data_dir` instead of the user's data directory
+    pub fn with_dir(data_dir: PathBuf, encryption: &EncryptionConfig) -> Self {
+        let _ = fs::create_dir_all(&data_dir);
+
+        let (cipher, mut read_only) = match Cipher::from_config(encryption) {
+            Ok(cipher) => (cipher, false),
+            Err(e) => {
+                eprintln!("History encryption key unavailable, not saving: {}", e);
+                (None, true)
+            }
+        };
+        let cipher = Arc::new(Mutex::new(cipher));
+
+        let db_path = data_dir.join(DATABASE_FILE);
+        let mut recovery_notice = None;
+        let reader = match db::open(&db_path) {
+            Ok(conn) => Some(conn),
+            Err(db::OpenError::Newer(version)) => {
+                eprintln!("{} uses schema {}, newer than this build, not touching it", db_path.display(), version);
+                None
+            }
+            Err(db::OpenError::Damaged(e)) => {
+                if let Some(backup) = legacy::backup_unreadable(&db_path, &e) {
+                    // SQLite would replay the old journal into the new database
+                    for suffix in ["-wal", "-shm"] {
+                        let _ = fs::rename(with_suffix(&db_path, suffix), with_suffix(&backup, suffix));
+                    }
+                }
+                recovery_notice = Some("Clipboard history was damaged and has been moved aside".to_string());
+                db::open(&db_path).ok()
+            }
+        };
+        if reader.is_none() {
+            read_only = true;
+        }
+
+        let (tx, rx) = mpsc::channel::<StorageOp>();
+        let write_cipher = Arc::clone(&cipher);
+        let pending = Pending::default();
+        let written = Arc::clone(&pending);
+
+        // Writer thread, each batch of queued changes is one transaction
+        thread::spawn(move || {
+            let Ok(mut conn) = db::open(&db_path) else {
+                return;
+            };
+            while let Ok(op) = rx.recv() {
+                let mut ops = vec![op];
+                ops.extend(rx.try_iter());
+
+                let mut cipher = write_cipher.lock();
+                if let Err(e) = apply(&mut conn, &mut cipher, &ops) {
+                    // Left pending, so they're still there to paste
+                    eprintln!("Failed to save history: {}", e);
+                    continue;
+                }
+
+                let mut pending = written.lock();
+                for op in &ops {
+                    if let StorageOp::Upsert(item) = op {
+                        // Unless the item was saved again in the meantime
+                        if pending.get(&item.id).is_some_and(|queued| Arc::ptr_eq(queued, item)) {
+                            pending.remove(&item.id);
+                        }
+                    }
+                }
+            }
+        });
+
+        Self {
+            data_dir,
+            write_sender: tx,
+            reader: reader.map(Mutex::new),
+            cipher,
+            read_only: AtomicBool::new(read_only),
+            recovery_notice: Mutex::new(recovery_notice),
+            pending,
+        }
+    }
+
+    /// Load the history, importing the old bincode file into an empty database
+    pub fn load_items(&self) -> Vec<ClipboardItem> {
+        let Some(reader) = &self.reader else {
+            return Vec::new();
+        };
+        let conn = reader.lock();
+        let mut cipher = self.cipher.lock();
+
+        let items = match db::load_items(&conn, &mut cipher, RESIDENT_TEXT) {
+            Ok(loaded) => {
+                if loaded.unreadable > 0 {
+                    let notice = format!(
+                        "{} clipboard history items could not be read, they were left in the database",
+                        loaded.unreadable,
+                    );
+                    self.recovery_notice.lock().get_or_insert(notice);
+                }
+                loaded.items
+            }
+            Err(e) => {
+                // Most likely the wrong key, leave the database alone
+                eprintln!("Cannot read history: {}", e);
+                self.read_only.store(true, Ordering::Relaxed);
+                return Vec::new();
+            }
+        };
+
+        let legacy_path = self.data_dir.join(LEGACY_FILE);
+        if items.is_empty() && legacy::exists(&legacy_path) {
+            return self.import_legacy(&legacy_path, &mut cipher);
+        }
+
+        // Rewrite rows stored before encryption was switched on (or off)
+        let encrypted = db::is_encrypted(&conn).ok().flatten();
+        if encrypted.is_some_and(|encrypted| encrypted != cipher.is_some()) {
+            self.send(StorageOp::Reseal);
+        }
+        items
+    }
+
+    fn import_legacy(&self, path: &Path, cipher: &mut Option<Cipher>) -> Vec<ClipboardItem> {
+        let imported = match legacy::read_history(path, cipher) {
+            Ok(Some(imported)) => imported,
+            Ok(None) => return Vec::new(),
+            Err(e) => {
+                eprintln!("Cannot import old history {}", e);
+                self.read_only.store(true, Ordering::Relaxed);
+                return Vec::new();
+            }
+        };
+
+        if let Some(snapshot) = &imported.recovered_from {
+            let notice = format!(
+                "Clipboard history was damaged, restored {} items from an earlier snapshot",
+                imported.items.len(),
+            );
+            eprintln!("{} ({})", notice, snapshot.display());
+            *self.recovery_notice.lock() = Some(notice);
+        }
+
+        let mut items = imported.items;
+        for item in &mut items {
+            item.recompute_hash();
+        }
+        // Oldest first, so the database order matches the history
+        for item in items.iter_mut().rev() {
+            self.insert(item);
+            self.unload(item);
+        }
+        legacy::mark_imported(path);
+        items
+    }
+
+    /// Save an item with its full content
+    pub fn insert(&self, item: &ClipboardItem) {
+        debug_assert!(!item.truncated, "saving the excerpt of an item");
+        if self.read_only.load(Ordering::Relaxed) {
+            return;
+        }
+        let item = Arc::new(item.clone());
+        self.pending.lock().insert(item.id.clone(), Arc::clone(&item));
+        self.send(StorageOp::Upsert(item));
+    }
+
+    /// Drop what can be read back from storage from a saved item, see
+    /// ClipboardItem::unload. Without storage everything stays in memory.
+    pub fn unload(&self, item: &mut ClipboardItem) {
+        if !self.read_only.load(Ordering::Relaxed) {
+            item.unload(RESIDENT_TEXT);
+        }
+    }
+
+    pub fn set_pinned(&self, id: &str, pinned: bool) {
+        self.send(StorageOp::SetPinned { id: id.to_string(), pinned });
+    }
+
+    pub fn delete(&self, ids: Vec<String>) {
+        if !ids.is_empty() {
+            self.send(StorageOp::Delete(ids));
+        }
+    }
+
+    /// Full size PNG of an image item, those aren't kept in memory once saved
+    pub fn load_image(&self, id: &str) -> Option<Vec<u8>> {
+        if let Some(item) = self.pending.lock().get(id) {
+            if let ClipboardContent::Image { png_data, .. } = &item.content {
+                return Some(png_data.clone());
+            }
+        }
+        let conn = self.reader.as_ref()?.lock();
+        db::load_image(&conn, &mut self.cipher.lock(), id)
+    }
+
+    /// Full content of a saved item, including the image
+    pub fn load_content(&self, id: &str) -> Option<ClipboardContent> {
+        if let Some(item) = self.pending.lock().get(id) {
+            return Some(item.content.clone());
+        }
+        let conn = self.reader.as_ref()?.lock();
+        db::load_content(&conn, &mut self.cipher.lock(), id)
+    }
+
+    /// Message about a recovery during load, returned only once
+    pub fn take_recovery_notice(&self) -> Option<String> {
+        self.recovery_notice.lock().take()
+    }
+
+    fn send(&self, op: StorageOp) {
+        if self.read_only.load(Ordering::Relaxed) {
+            return;
+        }
+        let _ = self.write_sender.send(op);
+    }
+}
+
+/// `path` with `suffix` appended to the file name, as SQLite names its journals
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn apply(conn: &mut Connection, cipher: &mut Option<Cipher>, ops: &[StorageOp]) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for op in ops {
+        match op {
+            StorageOp::Upsert(item) => db::upsert(&tx, cipher, item)?,
+            StorageOp::SetPinned { id, pinned } => db::set_pinned(&tx, id, *pinned)?,
+            StorageOp::Delete(ids) => db::delete(&tx, ids)?,
+            StorageOp::Reseal => db::reseal(&tx, cipher)?,
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
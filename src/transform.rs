@@ -0,0 +1,298 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+/// Conversions applied to text on the way to the clipboard, the stored item stays as it is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Transform {
+    // Paste HTML and RTF items without their formatting
+    PlainText,
+    Trim,
+    Upper,
+    Lower,
+    Title,
+    Snake,
+    Camel,
+    JsonPretty,
+    JsonMinify,
+    UrlEncode,
+    UrlDecode,
+    Base64Encode,
+    Base64Decode,
+    ShellEscape,
+    RegexEscape,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 15] = [
+        Transform::PlainText,
+        Transform::Trim,
+        Transform::Upper,
+        Transform::Lower,
+        Transform::Title,
+        Transform::Snake,
+        Transform::Camel,
+        Transform::JsonPretty,
+        Transform::JsonMinify,
+        Transform::UrlEncode,
+        Transform::UrlDecode,
+        Transform::Base64Encode,
+        Transform::Base64Decode,
+        Transform::ShellEscape,
+        Transform::RegexEscape,
+    ];
+
+    /// Name used on the command line and in actions
+    pub fn name(self) -> &'static str {
+        match self {
+            Transform::PlainText => "plain",
+            Transform::Trim => "trim",
+            Transform::Upper => "upper",
+            Transform::Lower => "lower",
+            Transform::Title => "title",
+            Transform::Snake => "snake",
+            Transform::Camel => "camel",
+            Transform::JsonPretty => "json-pretty",
+            Transform::JsonMinify => "json-minify",
+            Transform::UrlEncode => "url-encode",
+            Transform::UrlDecode => "url-decode",
+            Transform::Base64Encode => "base64-encode",
+            Transform::Base64Decode => "base64-decode",
+            Transform::ShellEscape => "shell-escape",
+            Transform::RegexEscape => "regex-escape",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|transform| transform.name() == name)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Transform::PlainText => "Plain Text",
+            Transform::Trim => "Trim Whitespace",
+            Transform::Upper => "UPPER CASE",
+            Transform::Lower => "lower case",
+            Transform::Title => "Title Case",
+            Transform::Snake => "snake_case",
+            Transform::Camel => "camelCase",
+            Transform::JsonPretty => "Pretty-print JSON",
+            Transform::JsonMinify => "Minify JSON",
+            Transform::UrlEncode => "URL-encode",
+            Transform::UrlDecode => "URL-decode",
+            Transform::Base64Encode => "Base64-encode",
+            Transform::Base64Decode => "Base64-decode",
+            Transform::ShellEscape => "Shell Escape",
+            Transform::RegexEscape => "Regex Escape",
+        }
+    }
+
+    /// Transform the plain text of an item, fails if the text doesn't fit
+    /// (invalid JSON, base64 that isn't text...)
+    pub fn apply(self, text: &str) -> Result<String, String> {
+        Ok(match self {
+            Transform::PlainText => text.to_string(),
+            Transform::Trim => text.lines().map(str::trim_end).collect::<Vec<_>>().join("\n").trim().to_string(),
+            Transform::Upper => text.to_uppercase(),
+            Transform::Lower => text.to_lowercase(),
+            Transform::Title => title_case(text),
+            Transform::Snake => words(text).iter().map(|word| word.to_lowercase()).collect::<Vec<_>>().join("_"),
+            Transform::Camel => words(text).iter().enumerate()
+                .map(|(i, word)| if i == 0 { word.to_lowercase() } else { capitalize(word) })
+                .collect(),
+            Transform::JsonPretty => serde_json::to_string_pretty(&parse_json(text)?).map_err(|e| e.to_string())?,
+            Transform::JsonMinify => serde_json::to_string(&parse_json(text)?).map_err(|e| e.to_string())?,
+            Transform::UrlEncode => url_encode(text),
+            Transform::UrlDecode => url_decode(text.trim())?,
+            Transform::Base64Encode => base64::engine::general_purpose::STANDARD.encode(text),
+            Transform::Base64Decode => {
+                let compact: String = text.split_whitespace().collect();
+                let engine = base64::engine::general_purpose::STANDARD;
+                let bytes = engine.decode(&compact)
+                    .or_else(|_| base64::engine::general_purpose::URL_SAFE.decode(&compact))
+                    .map_err(|e| format!("not base64: {}", e))?;
+                String::from_utf8(bytes).map_err(|_| "the decoded data isn't text".to_string())?
+            }
+            Transform::ShellEscape => shell_escape(text),
+            Transform::RegexEscape => regex::escape(text),
+        })
+    }
+}
+
+fn parse_json(text: &str) -> Result<serde_json::Value, String> {
+    serde_json::from_str(text).map_err(|e| format!("not JSON: {}", e))
+}
+
+/// Words of an identifier or phrase: split at non-alphanumerics and at
+/// lower to upper case changes, so `fooBar baz-qux` gives foo, Bar, baz, qux
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            previous_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lower {
+            words.push(std::mem::take(&mut current));
+        }
+        previous_lower = c.is_lowercase() || c.is_numeric();
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new(),
+    }
+}
+
+/// Capitalize every word, keeping the whitespace between them
+fn title_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut word_start = true;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            word_start = true;
+            out.push(c);
+        } else if word_start {
+            word_start = false;
+            out.extend(c.to_uppercase());
+        } else {
+            out.extend(c.to_lowercase());
+        }
+    }
+    out
+}
+
+fn url_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn url_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let byte = bytes.get(i + 1..i + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("invalid escape at position {}", i))?;
+                out.push(byte);
+                i += 3;
+            }
+            // Form encoding writes spaces as +
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).map_err(|_| "the decoded data isn't text".to_string())
+}
+
+/// Quote for POSIX shells, left alone if nothing in it needs quoting
+fn shell_escape(text: &str) -> String {
+    let safe = !text.is_empty()
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if safe {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(transform: Transform, text: &str) -> String {
+        transform.apply(text).unwrap()
+    }
+
+    #[test]
+    fn whitespace_and_case() {
+        assert_eq!(apply(Transform::PlainText, " a \n"), " a \n");
+        assert_eq!(apply(Transform::Trim, "  one  \n two \t\n\n"), "one\n two");
+        assert_eq!(apply(Transform::Upper, "Straße"), "STRASSE");
+        assert_eq!(apply(Transform::Lower, "HeLLo"), "hello");
+        assert_eq!(apply(Transform::Title, "hello  wORLD\nagain"), "Hello  World\nAgain");
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(apply(Transform::Snake, "fooBar baz-qux"), "foo_bar_baz_qux");
+        assert_eq!(apply(Transform::Snake, "HTTPServer2go"), "httpserver2go");
+        assert_eq!(apply(Transform::Camel, "foo_bar baz-QUX"), "fooBarBazQux");
+        assert_eq!(apply(Transform::Camel, ""), "");
+    }
+
+    #[test]
+    fn json() {
+        let text = "{\"a\": [1, 2], \"b\": null}";
+        assert_eq!(apply(Transform::JsonMinify, text), "{\"a\":[1,2],\"b\":null}");
+        assert_eq!(apply(Transform::JsonPretty, "[1]"), "[\n  1\n]");
+        let error = Transform::JsonPretty.apply("{\"a\": ").unwrap_err();
+        assert!(error.starts_with("not JSON"), "{}", error);
+        assert!(Transform::JsonMinify.apply("plain words").is_err());
+    }
+
+    #[test]
+    fn url() {
+        assert_eq!(apply(Transform::UrlEncode, "a b/c?d=é~"), "a%20b%2Fc%3Fd%3D%C3%A9~");
+        assert_eq!(apply(Transform::UrlDecode, " a%20b+c%2Fd%C3%A9 \n"), "a b c/dé");
+        // Truncated, non hex and non UTF-8 escapes
+        assert!(Transform::UrlDecode.apply("abc%2").is_err());
+        assert!(Transform::UrlDecode.apply("%zz").is_err());
+        assert!(Transform::UrlDecode.apply("%FF").is_err());
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(apply(Transform::Base64Encode, "hello?>"), "aGVsbG8/Pg==");
+        assert_eq!(apply(Transform::Base64Decode, "aGVs\nbG8/Pg=="), "hello?>");
+        // Falls back to the URL safe alphabet
+        assert_eq!(apply(Transform::Base64Decode, "aGVsbG8_Pg=="), "hello?>");
+        let error = Transform::Base64Decode.apply("not base64!").unwrap_err();
+        assert!(error.starts_with("not base64"), "{}", error);
+        // Valid base64 of bytes that aren't UTF-8
+        assert!(Transform::Base64Decode.apply("/w==").is_err());
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(apply(Transform::ShellEscape, "src/main.rs"), "src/main.rs");
+        assert_eq!(apply(Transform::ShellEscape, ""), "''");
+        assert_eq!(apply(Transform::ShellEscape, "it's $HOME"), r"'it'\''s $HOME'");
+        assert_eq!(apply(Transform::RegexEscape, "a.b*(c)"), r"a\.b\*\(c\)");
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for transform in Transform::ALL {
+            assert_eq!(Transform::from_name(transform.name()), Some(transform));
+        }
+        assert_eq!(Transform::from_name("nope"), None);
+    }
+}
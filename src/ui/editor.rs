@@ -0,0 +1,110 @@
+use crate::daemon::DaemonClient;
+use crate::models::{ClipboardContent, ClipboardItem};
+use crate::ui::preview::{apply_scheme, guess_language};
+use gtk4::prelude::*;
+use gtk4::{gdk, glib, Button, EventControllerKey, PolicyType, ScrolledWindow, WrapMode};
+use libadwaita as adw;
+use libadwaita::prelude::*;
+use sourceview5::prelude::*;
+use std::sync::Arc;
+
+/// Edit the text of a text item, then save it over the item or as a new one
+pub fn show(app: &adw::Application, client: Arc<DaemonClient>, item: &ClipboardItem) {
+    if item.is_sensitive() {
+        return;
+    }
+    // The list only has the start of long texts
+    let content = if item.truncated {
+        client.content(&item.id).ok().flatten()
+    } else {
+        Some(item.content.clone())
+    };
+    let Some(ClipboardContent::Text(text)) = content else {
+        return;
+    };
+
+    let window = adw::Window::builder()
+        .application(app)
+        .title("Edit Item")
+        .default_width(700)
+        .default_height(500)
+        .build();
+
+    let buffer = sourceview5::Buffer::new(None);
+    buffer.set_language(guess_language(&text).as_ref());
+    buffer.set_text(&text);
+    buffer.place_cursor(&buffer.start_iter());
+    apply_scheme(&buffer, adw::StyleManager::default().is_dark());
+
+    let view = sourceview5::View::with_buffer(&buffer);
+    view.set_show_line_numbers(true);
+    view.set_monospace(true);
+    view.set_wrap_mode(WrapMode::WordChar);
+    view.add_css_class("preview-text");
+
+    let scrolled = ScrolledWindow::builder()
+        .hscrollbar_policy(PolicyType::Never)
+        .vscrollbar_policy(PolicyType::Automatic)
+        .vexpand(true)
+        .child(&view)
+        .build();
+
+    let toasts = adw::ToastOverlay::new();
+    toasts.set_child(Some(&scrolled));
+
+    let header = adw::HeaderBar::new();
+    let save = Button::with_label("Save");
+    save.add_css_class("suggested-action");
+    save.set_tooltip_text(Some("Replace the item (Ctrl+S)"));
+    let save_new = Button::with_label("Save as New");
+    save_new.set_tooltip_text(Some("Keep the item and add the edited text (Ctrl+Shift+S)"));
+    header.pack_end(&save);
+    header.pack_end(&save_new);
+
+    let toolbar = adw::ToolbarView::new();
+    toolbar.add_top_bar(&header);
+    toolbar.set_content(Some(&toasts));
+    window.set_content(Some(&toolbar));
+
+    let editor = Editor { window: window.clone(), buffer, toasts, client, id: item.id.clone() };
+    let e = editor.clone();
+    save.connect_clicked(move |_| e.save(false));
+    let e = editor.clone();
+    save_new.connect_clicked(move |_| e.save(true));
+
+    let keys = EventControllerKey::new();
+    keys.connect_key_pressed(move |_, key, _, modifiers| {
+        let ctrl = modifiers.contains(gdk::ModifierType::CONTROL_MASK);
+        let shift = modifiers.contains(gdk::ModifierType::SHIFT_MASK);
+        match key {
+            gdk::Key::Escape => editor.window.close(),
+            gdk::Key::s | gdk::Key::S if ctrl => editor.save(shift),
+            _ => return glib::Propagation::Proceed,
+        }
+        glib::Propagation::Stop
+    });
+    window.add_controller(keys);
+
+    window.present();
+    view.grab_focus();
+}
+
+#[derive(Clone)]
+struct Editor {
+    window: adw::Window,
+    buffer: sourceview5::Buffer,
+    toasts: adw::ToastOverlay,
+    client: Arc<DaemonClient>,
+    id: String,
+}
+
+impl Editor {
+    fn save(&self, as_new: bool) {
+        let text = self.buffer.text(&self.buffer.start_iter(), &self.buffer.end_iter(), false);
+        match self.client.edit_text(&self.id, text.to_string(), as_new) {
+            // The popup follows the change through the daemon's events
+            Ok(_) => self.window.close(),
+            Err(e) => self.toasts.add_toast(adw::Toast::new(&format!("Failed to save: {}", e))),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use crate::daemon::DaemonClient;
+use crate::mime;
+use crate::models::{ClipboardContent, ClipboardItem};
+use chrono::Local;
+use gtk4::prelude::*;
+use gtk4::{
+    gdk, gio, glib, Align, Button, ContentFit, EventControllerKey, EventControllerScroll,
+    EventControllerScrollFlags, GestureDrag, Picture, PolicyType, ScrolledWindow,
+};
+use libadwaita as adw;
+use libadwaita::prelude::*;
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+const ZOOM_STEP: f64 = 1.25;
+const MIN_ZOOM: f64 = 0.05;
+const MAX_ZOOM: f64 = 16.0;
+
+/// Full size view of an image item with zoom, pan, save and copy as file
+pub fn show(app: &adw::Application, client: &DaemonClient, item: &ClipboardItem) {
+    let ClipboardContent::Image { width, height, .. } = item.content else {
+        return;
+    };
+    // Items loaded from the database only carry their thumbnail
+    let png_data = match client.image_png(&item.id) {
+        Ok(Some(png_data)) => png_data,
+        Ok(None) => {
+            eprintln!("Image data not found for {}", item.id);
+            return;
+        }
+        Err(e) => {
+            eprintln!("Failed to load image: {}", e);
+            return;
+        }
+    };
+    let texture = match gdk::Texture::from_bytes(&glib::Bytes::from(&png_data)) {
+        Ok(texture) => texture,
+        Err(e) => {
+            eprintln!("Failed to decode image: {}", e);
+            return;
+        }
+    };
+
+    let window = adw::Window::builder()
+        .application(app)
+        .title("Image")
+        .default_width(900)
+        .default_height(650)
+        .build();
+
+    let title = adw::WindowTitle::new(
+        "Image",
+        &format!("{}×{} · PNG · {}", width, height, glib::format_size(png_data.len() as u64)),
+    );
+    let header = adw::HeaderBar::new();
+    header.set_title_widget(Some(&title));
+
+    let picture = Picture::for_paintable(&texture);
+    picture.set_can_shrink(true);
+    picture.set_content_fit(ContentFit::Contain);
+
+    let scrolled = ScrolledWindow::builder()
+        .hscrollbar_policy(PolicyType::Automatic)
+        .vscrollbar_policy(PolicyType::Automatic)
+        .vexpand(true)
+        .child(&picture)
+        .build();
+
+    let view = ZoomView { picture: picture.clone(), scrolled: scrolled.clone(), zoom: Rc::new(Cell::new(None)) };
+    view.fit();
+
+    let zoom_out = Button::from_icon_name("zoom-out-symbolic");
+    zoom_out.set_tooltip_text(Some("Zoom out (-)"));
+    let v = view.clone();
+    zoom_out.connect_clicked(move |_| v.zoom_by(1.0 / ZOOM_STEP));
+    let zoom_fit = Button::from_icon_name("zoom-fit-best-symbolic");
+    zoom_fit.set_tooltip_text(Some("Fit to window (0)"));
+    let v = view.clone();
+    zoom_fit.connect_clicked(move |_| v.fit());
+    let zoom_in = Button::from_icon_name("zoom-in-symbolic");
+    zoom_in.set_tooltip_text(Some("Zoom in (+)"));
+    let v = view.clone();
+    zoom_in.connect_clicked(move |_| v.zoom_by(ZOOM_STEP));
+    header.pack_start(&zoom_out);
+    header.pack_start(&zoom_fit);
+    header.pack_start(&zoom_in);
+
+    let toasts = adw::ToastOverlay::new();
+    toasts.set_child(Some(&scrolled));
+
+    let png_data = Rc::new(png_data);
+    let save_name = format!("clipboard-{}.png", item.timestamp.with_timezone(&Local).format("%Y-%m-%d-%H%M%S"));
+    let cache_name = format!("{:016x}.png", item.content_hash);
+
+    let save = Button::from_icon_name("document-save-symbolic");
+    save.set_tooltip_text(Some("Save as file (Ctrl+S)"));
+    let (w, t, data, name) = (window.clone(), toasts.clone(), Rc::clone(&png_data), save_name.clone());
+    save.connect_clicked(move |_| save_as(&w, &t, Rc::clone(&data), &name));
+    let copy_uri = Button::from_icon_name("edit-copy-symbolic");
+    copy_uri.set_tooltip_text(Some("Copy as file (Ctrl+C)"));
+    let (w, t, data, name) = (window.clone(), toasts.clone(), Rc::clone(&png_data), cache_name.clone());
+    copy_uri.connect_clicked(move |_| copy_as_file(&w, &t, &data, &name));
+    header.pack_end(&copy_uri);
+    header.pack_end(&save);
+
+    let toolbar = adw::ToolbarView::new();
+    toolbar.add_top_bar(&header);
+    toolbar.set_content(Some(&toasts));
+    window.set_content(Some(&toolbar));
+
+    // Ctrl+scroll zooms, plain scrolling moves around
+    let scroll = EventControllerScroll::new(EventControllerScrollFlags::VERTICAL);
+    let v = view.clone();
+    scroll.connect_scroll(move |controller, _, dy| {
+        if !controller.current_event_state().contains(gdk::ModifierType::CONTROL_MASK) {
+            return glib::Propagation::Proceed;
+        }
+        v.zoom_by(if dy < 0.0 { ZOOM_STEP } else { 1.0 / ZOOM_STEP });
+        glib::Propagation::Stop
+    });
+    scrolled.add_controller(scroll);
+
+    // Drag to pan a zoomed image
+    let drag = GestureDrag::new();
+    let start = Rc::new(Cell::new((0.0, 0.0)));
+    let (s, start_drag) = (scrolled.clone(), Rc::clone(&start));
+    drag.connect_drag_begin(move |_, _, _| {
+        start_drag.set((s.hadjustment().value(), s.vadjustment().value()));
+    });
+    let s = scrolled.clone();
+    drag.connect_drag_update(move |_, dx, dy| {
+        let (x, y) = start.get();
+        s.hadjustment().set_value(x - dx);
+        s.vadjustment().set_value(y - dy);
+    });
+    scrolled.add_controller(drag);
+
+    let keys = EventControllerKey::new();
+    let w = window.clone();
+    keys.connect_key_pressed(move |_, key, _, modifiers| {
+        let ctrl = modifiers.contains(gdk::ModifierType::CONTROL_MASK);
+        match key {
+            gdk::Key::Escape => w.close(),
+            gdk::Key::plus | gdk::Key::equal | gdk::Key::KP_Add => view.zoom_by(ZOOM_STEP),
+            gdk::Key::minus | gdk::Key::KP_Subtract => view.zoom_by(1.0 / ZOOM_STEP),
+            gdk::Key::_0 | gdk::Key::KP_0 => view.fit(),
+            gdk::Key::_1 | gdk::Key::KP_1 => view.set_zoom(1.0),
+            gdk::Key::s if ctrl => save_as(&w, &toasts, Rc::clone(&png_data), &save_name),
+            gdk::Key::c if ctrl => copy_as_file(&w, &toasts, &png_data, &cache_name),
+            _ => return glib::Propagation::Proceed,
+        }
+        glib::Propagation::Stop
+    });
+    window.add_controller(keys);
+
+    window.present();
+}
+
+/// Scale of the picture, None while it is fitted to the window
+#[derive(Clone)]
+struct ZoomView {
+    picture: Picture,
+    scrolled: ScrolledWindow,
+    zoom: Rc<Cell<Option<f64>>>,
+}
+
+impl ZoomView {
+    fn fit(&self) {
+        self.zoom.set(None);
+        self.picture.set_size_request(-1, -1);
+        self.picture.set_halign(Align::Fill);
+        self.picture.set_valign(Align::Fill);
+    }
+
+    fn zoom_by(&self, factor: f64) {
+        let current = self.zoom.get().unwrap_or_else(|| self.fitted_scale());
+        self.set_zoom(current * factor);
+    }
+
+    fn set_zoom(&self, zoom: f64) {
+        let Some((width, height)) = self.image_size() else {
+            return;
+        };
+        let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+
+        // Keep the point in the middle of the view where it was
+        let (h, v) = (self.scrolled.hadjustment(), self.scrolled.vadjustment());
+        let center_x = (h.value() + h.page_size() / 2.0) / h.upper().max(1.0);
+        let center_y = (v.value() + v.page_size() / 2.0) / v.upper().max(1.0);
+
+        self.zoom.set(Some(zoom));
+        let (scaled_width, scaled_height) = ((width * zoom).round(), (height * zoom).round());
+        self.picture.set_size_request(scaled_width as i32, scaled_height as i32);
+        self.picture.set_halign(Align::Center);
+        self.picture.set_valign(Align::Center);
+
+        // The adjustments only know the new size after the next layout
+        let (h, v) = (h.clone(), v.clone());
+        glib::idle_add_local_once(move || {
+            h.set_value(center_x * h.upper() - h.page_size() / 2.0);
+            v.set_value(center_y * v.upper() - v.page_size() / 2.0);
+        });
+    }
+
+    /// Scale the fitted image is shown at, so zooming starts from what is on screen
+    fn fitted_scale(&self) -> f64 {
+        let Some((width, height)) = self.image_size() else {
+            return 1.0;
+        };
+        let shown_width = self.scrolled.width() as f64 / width;
+        let shown_height = self.scrolled.height() as f64 / height;
+        shown_width.min(shown_height).min(1.0)
+    }
+
+    fn image_size(&self) -> Option<(f64, f64)> {
+        let paintable = self.picture.paintable()?;
+        Some((paintable.intrinsic_width() as f64, paintable.intrinsic_height() as f64))
+            .filter(|&(width, height)| width > 0.0 && height > 0.0)
+    }
+}
+
+fn save_as(window: &adw::Window, toasts: &adw::ToastOverlay, png_data: Rc<Vec<u8>>, name: &str) {
+    let dialog = gtk4::FileDialog::builder()
+        .title("Save Image")
+        .initial_name(name)
+        .modal(true)
+        .build();
+    let toasts = toasts.clone();
+    dialog.save(Some(window), gio::Cancellable::NONE, move |result| {
+        // Cancelling the dialog also ends up here
+        let Ok(file) = result else {
+            return;
+        };
+        let Some(path) = file.path() else {
+            return;
+        };
+        match std::fs::write(&path, png_data.as_slice()) {
+            Ok(()) => toasts.add_toast(adw::Toast::new(&format!("Saved to {}", path.display()))),
+            Err(e) => toasts.add_toast(adw::Toast::new(&format!("Failed to save image: {}", e))),
+        }
+    });
+}
+
+/// Put the image on the clipboard as a file, for apps that take files but not image data
+fn copy_as_file(window: &adw::Window, toasts: &adw::ToastOverlay, png_data: &[u8], name: &str) {
+    let path = match write_cache_file(png_data, name) {
+        Ok(path) => path,
+        Err(e) => {
+            toasts.add_toast(adw::Toast::new(&format!("Failed to copy image: {}", e)));
+            return;
+        }
+    };
+    let uri = gio::File::for_path(&path).uri().to_string();
+
+    // Same targets as pasting a files item from the history
+    let providers = [
+        gdk::ContentProvider::for_bytes(mime::URI_LIST, &glib::Bytes::from_owned(format!("{}\r\n", uri))),
+        gdk::ContentProvider::for_bytes(
+            mime::GNOME_COPIED_FILES,
+            &glib::Bytes::from_owned(format!("copy\n{}", uri)),
+        ),
+        gdk::ContentProvider::for_bytes(
+            mime::TEXT_PLAIN,
+            &glib::Bytes::from_owned(path.to_string_lossy().into_owned()),
+        ),
+    ];
+    if let Err(e) = window.clipboard().set_content(Some(&gdk::ContentProvider::new_union(&providers))) {
+        toasts.add_toast(adw::Toast::new(&format!("Failed to copy image: {}", e)));
+        return;
+    }
+    toasts.add_toast(adw::Toast::new("Copied as file"));
+}
+
+/// Copies are written to the cache directory, named after the content so
+/// copying the same image again reuses the file
+fn write_cache_file(png_data: &[u8], name: &str) -> std::io::Result<PathBuf> {
+    let mut dir = dirs::cache_dir().unwrap_or_else(std::env::temp_dir);
+    dir.push("clipboard_manager");
+    dir.push("images");
+    std::fs::create_dir_all(&dir)?;
+
+    let path = dir.join(name);
+    if !path.exists() {
+        std::fs::write(&path, png_data)?;
+    }
+    Ok(path)
+}
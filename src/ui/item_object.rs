@@ -0,0 +1,114 @@
+use crate::models::{ClipboardContent, ClipboardItem};
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use gtk4::{gdk, gio, glib};
+use std::cell::{Ref, RefCell};
+use std::collections::HashSet;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default)]
+    pub struct ItemObject {
+        pub item: RefCell<Option<ClipboardItem>>,
+        // Decoded on first display, handed on when the item is updated
+        pub thumbnail: RefCell<Option<gdk::Texture>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ItemObject {
+        const NAME: &'static str = "ClipboardItemObject";
+        type Type = super::ItemObject;
+    }
+
+    impl ObjectImpl for ItemObject {}
+}
+
+glib::wrapper! {
+    /// A history item in the popup's list model
+    pub struct ItemObject(ObjectSubclass<imp::ItemObject>);
+}
+
+impl ItemObject {
+    pub fn new(item: ClipboardItem) -> Self {
+        let obj: Self = glib::Object::new();
+        obj.imp().item.replace(Some(item));
+        obj
+    }
+
+    /// A new object for a changed item. The list only redraws rows whose object
+    /// changed, the decoded thumbnail is kept if the content is the same.
+    pub fn updated(&self, item: ClipboardItem) -> Self {
+        let same_content = self.item().content_hash == item.content_hash;
+        let obj = Self::new(item);
+        if same_content {
+            obj.imp().thumbnail.replace(self.imp().thumbnail.borrow().clone());
+        }
+        obj
+    }
+
+    pub fn item(&self) -> Ref<'_, ClipboardItem> {
+        Ref::map(self.imp().item.borrow(), |item| item.as_ref().expect("set in ItemObject::new"))
+    }
+
+    pub fn id(&self) -> String {
+        self.item().id.clone()
+    }
+
+    /// Thumbnail of an image item, decoded once
+    pub fn thumbnail(&self) -> Option<gdk::Texture> {
+        if let Some(texture) = self.imp().thumbnail.borrow().as_ref() {
+            return Some(texture.clone());
+        }
+
+        let texture = match &self.item().content {
+            ClipboardContent::Image { thumbnail_png, .. } => {
+                gdk::Texture::from_bytes(&glib::Bytes::from(thumbnail_png)).ok()?
+            }
+            _ => return None,
+        };
+        self.imp().thumbnail.replace(Some(texture.clone()));
+        Some(texture)
+    }
+}
+
+/// Bring `store` in line with `items`, touching only the entries that were
+/// added, removed, moved or changed
+pub fn sync_store(store: &gio::ListStore, items: Vec<ClipboardItem>) {
+    let wanted: HashSet<&str> = items.iter().map(|item| item.id.as_str()).collect();
+    for position in (0..store.n_items()).rev() {
+        if !wanted.contains(object_at(store, position).id().as_str()) {
+            store.remove(position);
+        }
+    }
+
+    for (position, item) in (0u32..).zip(items) {
+        if position < store.n_items() && unchanged(&object_at(store, position).item(), &item) {
+            continue;
+        }
+
+        // Pinning moves items, find the object further down
+        let existing = (position..store.n_items()).find(|&p| object_at(store, p).id() == item.id);
+        let obj = match existing {
+            Some(from) => {
+                let obj = object_at(store, from);
+                store.remove(from);
+                if unchanged(&obj.item(), &item) { obj } else { obj.updated(item) }
+            }
+            None => ItemObject::new(item),
+        };
+        store.insert(position, &obj);
+    }
+}
+
+fn object_at(store: &gio::ListStore, position: u32) -> ItemObject {
+    store.item(position).and_downcast().expect("store only holds ItemObjects")
+}
+
+fn unchanged(shown: &ClipboardItem, item: &ClipboardItem) -> bool {
+    shown.id == item.id
+        && shown.pinned == item.pinned
+        && shown.content_hash == item.content_hash
+        && shown.timestamp == item.timestamp
+        && shown.is_sensitive() == item.is_sensitive()
+}
@@ -1,53 +1,90 @@
-use crate::models::{ClipboardContent, ClipboardItem};
+use crate::classify::TextKind;
+use crate::color::{Color, ColorFormat};
+use crate::models::{ClipboardContent, Selection, SourceApp};
+use crate::transform::Transform;
+use crate::ui::item_object::ItemObject;
+use crate::ui::relative_time::relative_time;
+use chrono::{DateTime, Local, Utc};
 use gtk4::prelude::*;
-use gtk4::{Box, Button, Label, ListBoxRow, Orientation, Image, Align, Picture};
-use gtk4::gdk_pixbuf::Pixbuf;
-use gtk4::glib::Bytes;
+use gtk4::{gdk, gio, glib, Box, Button, DrawingArea, Label, MenuButton, Orientation, Image, Align, Picture};
 
-pub fn create_list_row(item: &ClipboardItem) -> ListBoxRow {
-    let row = ListBoxRow::new();
-    row.add_css_class("clipboard-item");
-    
-    unsafe {
-        row.set_data("item_id", item.id.clone());
-    }
-    
+// Window action taking the item id, triggered by the pin button
+pub const TOGGLE_PIN_ACTION: &str = "popup.toggle-pin";
+
+// Window action taking the item id, opens an image item in the viewer
+pub const VIEW_IMAGE_ACTION: &str = "popup.view-image";
+
+// Window action taking the item id and a ColorFormat name, copies a color item converted
+pub const COPY_COLOR_ACTION: &str = "popup.copy-color";
+
+// Window action taking the item id and a Transform name, pastes the converted text
+pub const PASTE_TRANSFORMED_ACTION: &str = "popup.paste-transformed";
+
+// Window action taking the item id, opens a text item in the editor
+pub const EDIT_ACTION: &str = "popup.edit";
+
+/// Row content for the popup's list view
+pub fn create_list_row(obj: &ItemObject) -> Box {
+    let item = obj.item();
     let hbox = Box::new(Orientation::Horizontal, 12);
+    hbox.add_css_class("clipboard-item");
     hbox.set_margin_top(8);
     hbox.set_margin_bottom(8);
     hbox.set_margin_start(12);
     hbox.set_margin_end(12);
     
     match &item.content {
+        // Never show secrets, only that something is there
+        _ if item.is_sensitive() => {
+            let icon = Image::from_icon_name("dialog-password-symbolic");
+            icon.set_pixel_size(32);
+            icon.add_css_class("file-icon");
+            hbox.append(&icon);
+            hbox.append(&create_text_preview("••••••••", Some("SENSITIVE"), item.timestamp, item.source_app.as_ref()));
+        }
         ClipboardContent::Text(text) => {
-            let vbox = Box::new(Orientation::Vertical, 4);
-            vbox.set_hexpand(true);
-            
-            let preview = truncate_string(text, 150);
-            
-            let label = Label::new(Some(&preview));
-            label.set_xalign(0.0);
-            label.set_wrap(true);
-            label.set_wrap_mode(gtk4::pango::WrapMode::WordChar);
-            label.set_max_width_chars(50);
-            label.set_lines(3);
-            label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
-            label.add_css_class("item-text");
-            vbox.append(&label);
-            
-            let timestamp_label = Label::new(Some(&item.timestamp));
-            timestamp_label.add_css_class("timestamp");
-            timestamp_label.set_xalign(0.0);
-            vbox.append(&timestamp_label);
+            if let Some(color) = item.color {
+                hbox.append(&create_color_swatch(color, &item.id));
+            } else if let Some(icon_name) = kind_icon(item.kind) {
+                let icon = Image::from_icon_name(icon_name);
+                icon.set_pixel_size(16);
+                icon.set_valign(Align::Start);
+                icon.add_css_class("file-icon");
+                icon.set_tooltip_text(Some(item.kind.name()));
+                hbox.append(&icon);
+            }
+            let badge = (item.source == Selection::Primary).then_some("PRIMARY");
+            hbox.append(&create_text_preview(text, badge, item.timestamp, item.source_app.as_ref()));
+        }
+        ClipboardContent::Html { text, html } => {
+            // Some sources offer HTML without a plain text fallback
+            let preview = if text.trim().is_empty() { strip_tags(html) } else { text.clone() };
+            hbox.append(&create_text_preview(&preview, Some("HTML"), item.timestamp, item.source_app.as_ref()));
+        }
+        ClipboardContent::Rtf { text, .. } => {
+            hbox.append(&create_text_preview(text, Some("RTF"), item.timestamp, item.source_app.as_ref()));
+        }
+        ClipboardContent::Files(uris) => {
+            let icon_name = if uris.len() == 1 { "text-x-generic-symbolic" } else { "folder-documents-symbolic" };
+            let icon = Image::from_icon_name(icon_name);
+            icon.set_pixel_size(32);
+            icon.add_css_class("file-icon");
+            hbox.append(&icon);
             
-            hbox.append(&vbox);
+            let names: Vec<String> = uris.iter()
+                .map(|uri| {
+                    let path = crate::mime::uri_to_path(uri);
+                    path.rsplit('/').find(|part| !part.is_empty()).unwrap_or(&path).to_string()
+                })
+                .collect();
+            let badge = format!("{} file{}", uris.len(), if uris.len() == 1 { "" } else { "s" });
+            hbox.append(&create_text_preview(&names.join("\n"), Some(&badge), item.timestamp, item.source_app.as_ref()));
         }
-        ClipboardContent::Image { thumbnail_png, width, height, .. } => {
+        ClipboardContent::Image { width, height, .. } => {
             let vbox = Box::new(Orientation::Vertical, 4);
             vbox.set_hexpand(true);
             
-            // Create image from thumbnail PNG
-            let image_widget = create_image_from_png(thumbnail_png);
+            let image_widget = create_picture(obj.thumbnail());
             image_widget.set_halign(Align::Start);
             image_widget.add_css_class("thumbnail");
             vbox.append(&image_widget);
@@ -55,19 +92,34 @@ pub fn create_list_row(item: &ClipboardItem) -> ListBoxRow {
             // Show dimensions and timestamp
             let info_box = Box::new(Orientation::Horizontal, 8);
             
+            if let Some(source) = &item.source_app {
+                info_box.append(&create_source_icon(source));
+            }
+
             let dim_label = Label::new(Some(&format!("{}×{}", width, height)));
             dim_label.add_css_class("image-dimensions");
             info_box.append(&dim_label);
             
-            let timestamp_label = Label::new(Some(&item.timestamp));
-            timestamp_label.add_css_class("timestamp");
-            info_box.append(&timestamp_label);
+            info_box.append(&create_timestamp_label(item.timestamp));
             
             vbox.append(&info_box);
             hbox.append(&vbox);
+
+            let view_button = Button::from_icon_name("zoom-in-symbolic");
+            view_button.add_css_class("pin-button");
+            view_button.add_css_class("flat");
+            view_button.set_valign(Align::Center);
+            view_button.set_tooltip_text(Some("View image (Ctrl+O)"));
+            view_button.set_action_name(Some(VIEW_IMAGE_ACTION));
+            view_button.set_action_target_value(Some(&item.id.to_variant()));
+            hbox.append(&view_button);
         }
     }
     
+    if !item.is_sensitive() && !item.is_image() {
+        hbox.append(&create_transform_menu(&item.id));
+    }
+
     // Pin Button
     let pin_button = Button::new();
     pin_button.add_css_class("pin-button");
@@ -81,36 +133,203 @@ pub fn create_list_row(item: &ClipboardItem) -> ListBoxRow {
     if item.pinned {
         pin_button.add_css_class("pinned");
     }
-    
-    let row_weak = row.downgrade();
-    pin_button.connect_clicked(move |_| {
-        if let Some(row) = row_weak.upgrade() {
-            unsafe {
-                row.set_data("is_pin_click", true);
-            }
-            row.activate();
-        }
-    });
+    pin_button.set_action_name(Some(TOGGLE_PIN_ACTION));
+    pin_button.set_action_target_value(Some(&item.id.to_variant()));
     
     hbox.append(&pin_button);
-    row.set_child(Some(&hbox));
-    row
+    hbox
 }
 
-fn create_image_from_png(png_data: &[u8]) -> Picture {
-    let picture = Picture::new();
+fn create_text_preview(text: &str, badge: Option<&str>, timestamp: DateTime<Utc>, source: Option<&SourceApp>) -> Box {
+    let vbox = Box::new(Orientation::Vertical, 4);
+    vbox.set_hexpand(true);
+    
+    let preview = truncate_string(text, 150);
+    
+    let label = Label::new(Some(&preview));
+    label.set_xalign(0.0);
+    label.set_wrap(true);
+    label.set_wrap_mode(gtk4::pango::WrapMode::WordChar);
+    label.set_max_width_chars(50);
+    label.set_lines(3);
+    label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    label.add_css_class("item-text");
+    vbox.append(&label);
+    
+    let info_box = Box::new(Orientation::Horizontal, 8);
+    
+    if let Some(source) = source {
+        info_box.append(&create_source_icon(source));
+    }
+
+    if let Some(badge) = badge {
+        let badge_label = Label::new(Some(badge));
+        badge_label.add_css_class("format-badge");
+        info_box.append(&badge_label);
+    }
     
-    // Load PNG data into Pixbuf
-    let bytes = Bytes::from(png_data);
-    let stream = gtk4::gio::MemoryInputStream::from_bytes(&bytes);
+    let timestamp_label = create_timestamp_label(timestamp);
+    timestamp_label.set_xalign(0.0);
+    info_box.append(&timestamp_label);
     
-    if let Ok(pixbuf) = Pixbuf::from_stream(&stream, gtk4::gio::Cancellable::NONE) {
-        let texture = gtk4::gdk::Texture::for_pixbuf(&pixbuf);
-        picture.set_paintable(Some(&texture));
+    vbox.append(&info_box);
+    vbox
+}
+
+/// Relative time that keeps itself current while the row is shown
+fn create_timestamp_label(timestamp: DateTime<Utc>) -> Label {
+    let label = Label::new(Some(&relative_time(timestamp, Local::now())));
+    label.add_css_class("timestamp");
+    label.set_tooltip_text(Some(&timestamp.with_timezone(&Local).format("%c").to_string()));
+
+    // Rows are recycled by the list view, the timer ends with the label
+    let label_weak = label.downgrade();
+    glib::timeout_add_seconds_local(30, move || {
+        let Some(label) = label_weak.upgrade() else {
+            return glib::ControlFlow::Break;
+        };
+        label.set_text(&relative_time(timestamp, Local::now()));
+        glib::ControlFlow::Continue
+    });
+    label
+}
+
+/// Icon of the application an item was copied from, named in the tooltip
+fn create_source_icon(source: &SourceApp) -> Image {
+    // WM_CLASS is often the icon name, sometimes capitalized
+    let lowercase = source.id.to_lowercase();
+    let icon_name = gdk::Display::default()
+        .map(|display| gtk4::IconTheme::for_display(&display))
+        .and_then(|theme| [source.id.as_str(), lowercase.as_str()].into_iter().find(|name| theme.has_icon(name)))
+        .unwrap_or("application-x-executable-symbolic");
+
+    let icon = Image::from_icon_name(icon_name);
+    icon.set_pixel_size(16);
+    icon.add_css_class("source-icon");
+    let tooltip = match &source.title {
+        Some(title) => format!("Copied from {} ({})", title, source.id),
+        None => format!("Copied from {}", source.id),
+    };
+    icon.set_tooltip_text(Some(&tooltip));
+    icon
+}
+
+/// Swatch of a color item, opening a menu to copy it in other notations
+fn create_color_swatch(color: Color, id: &str) -> MenuButton {
+    let swatch = DrawingArea::new();
+    swatch.set_content_width(24);
+    swatch.set_content_height(24);
+    swatch.set_draw_func(move |_, cr, width, height| {
+        let (width, height) = (width as f64, height as f64);
+        // Checkerboard so transparency shows
+        cr.set_source_rgb(0.8, 0.8, 0.8);
+        cr.rectangle(0.0, 0.0, width, height);
+        let _ = cr.fill();
+        cr.set_source_rgb(0.6, 0.6, 0.6);
+        cr.rectangle(0.0, 0.0, width / 2.0, height / 2.0);
+        cr.rectangle(width / 2.0, height / 2.0, width / 2.0, height / 2.0);
+        let _ = cr.fill();
+
+        let (r, g, b, a) = color.to_rgba_f64();
+        cr.set_source_rgba(r, g, b, a);
+        cr.rectangle(0.0, 0.0, width, height);
+        let _ = cr.fill();
+    });
+    swatch.add_css_class("color-swatch");
+
+    let menu = gio::Menu::new();
+    for format in ColorFormat::ALL {
+        let entry = gio::MenuItem::new(Some(format.label()), None);
+        entry.set_action_and_target_value(
+            Some(COPY_COLOR_ACTION),
+            Some(&(id, format.name()).to_variant()),
+        );
+        menu.append_item(&entry);
     }
+
+    let button = MenuButton::new();
+    button.set_child(Some(&swatch));
+    button.set_menu_model(Some(&menu));
+    button.set_valign(Align::Center);
+    button.add_css_class("flat");
+    button.set_tooltip_text(Some(&color.format(ColorFormat::Hex)));
+    button
+}
+
+/// "Paste as" menu with the text transforms, grouped by what they do
+fn create_transform_menu(id: &str) -> MenuButton {
+    use Transform::*;
+    let groups: [&[Transform]; 5] = [
+        &[PlainText, Trim],
+        &[Upper, Lower, Title, Snake, Camel],
+        &[JsonPretty, JsonMinify],
+        &[UrlEncode, UrlDecode, Base64Encode, Base64Decode],
+        &[ShellEscape, RegexEscape],
+    ];
+
+    let menu = gio::Menu::new();
+    for group in groups {
+        let section = gio::Menu::new();
+        for transform in group {
+            let entry = gio::MenuItem::new(Some(transform.label()), None);
+            entry.set_action_and_target_value(
+                Some(PASTE_TRANSFORMED_ACTION),
+                Some(&(id, transform.name()).to_variant()),
+            );
+            section.append_item(&entry);
+        }
+        menu.append_section(None, &section);
+    }
+
+    let button = MenuButton::new();
+    button.set_icon_name("view-more-symbolic");
+    button.set_menu_model(Some(&menu));
+    button.set_tooltip_text(Some("Paste as"));
+    button.set_valign(Align::Center);
+    button.add_css_class("pin-button");
+    button.add_css_class("flat");
+    button
+}
+
+/// Icon for a detected kind of text, plain text has none
+fn kind_icon(kind: TextKind) -> Option<&'static str> {
+    match kind {
+        TextKind::Plain => None,
+        TextKind::Url => Some("web-browser-symbolic"),
+        TextKind::Email => Some("mail-unread-symbolic"),
+        TextKind::Color => Some("color-select-symbolic"),
+        TextKind::Path => Some("folder-symbolic"),
+        TextKind::Json | TextKind::Yaml => Some("text-x-generic-symbolic"),
+        TextKind::Code => Some("utilities-terminal-symbolic"),
+        TextKind::Number => Some("accessories-calculator-symbolic"),
+        TextKind::Phone => Some("call-start-symbolic"),
+        TextKind::Uuid => Some("emblem-documents-symbolic"),
+    }
+}
+
+/// Crude tag stripper for previews of HTML without a text fallback
+fn strip_tags(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => {
+                in_tag = false;
+                out.push(' ');
+            }
+            c if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn create_picture(texture: Option<gdk::Texture>) -> Picture {
+    let picture = Picture::new();
+    picture.set_paintable(texture.as_ref());
     
     picture.set_can_shrink(true);
-    picture.set_keep_aspect_ratio(true);
     picture.set_content_fit(gtk4::ContentFit::Contain);
     
     picture
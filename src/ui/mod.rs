@@ -1,3 +1,9 @@
 pub mod window;
+pub mod editor;
+pub mod item_object;
+pub mod image_viewer;
 pub mod list_item;
-pub mod styles;
\ No newline at end of file
+pub mod preferences;
+pub mod preview;
+pub mod relative_time;
+pub mod styles;
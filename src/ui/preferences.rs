@@ -0,0 +1,255 @@
+use crate::config::{Config, KeySource, PasteKey, PrimaryMode, SensitiveAction};
+use crate::crypto::PASSPHRASE_ENV;
+use libadwaita as adw;
+use libadwaita::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Changes are written to the config file right away, the daemon and the
+/// popup pick them up through their file monitors
+#[derive(Clone)]
+struct Settings {
+    config: Rc<RefCell<Config>>,
+}
+
+impl Settings {
+    fn update(&self, change: impl FnOnce(&mut Config)) {
+        let mut config = self.config.borrow_mut();
+        change(&mut config);
+        if let Err(e) = config.save() {
+            eprintln!("Failed to save config: {}", e);
+        }
+    }
+}
+
+pub fn show(app: &adw::Application) {
+    // Saving defaults over a file that doesn't parse would lose it
+    let config = match Config::try_load() {
+        Ok(config) => config,
+        Err(e) => {
+            let dialog = adw::MessageDialog::builder()
+                .application(app)
+                .heading("Invalid Config File")
+                .body(format!("Fix or remove it to change the preferences here.\n\n{}", e))
+                .build();
+            dialog.add_response("close", "Close");
+            dialog.present();
+            return;
+        }
+    };
+    let settings = Settings {
+        config: Rc::new(RefCell::new(config.clone())),
+    };
+
+    let window = adw::PreferencesWindow::builder()
+        .application(app)
+        .title("Clipboard Manager Preferences")
+        .search_enabled(false)
+        .default_width(520)
+        .default_height(640)
+        .build();
+
+    window.add(&general_page(&settings, &config));
+    window.add(&privacy_page(&settings, &config));
+    window.present();
+}
+
+fn general_page(settings: &Settings, config: &Config) -> adw::PreferencesPage {
+    let page = adw::PreferencesPage::builder()
+        .title("General")
+        .icon_name("preferences-system-symbolic")
+        .build();
+
+    let history = adw::PreferencesGroup::builder().title("History").build();
+    let s = settings.clone();
+    history.add(&spin_row("Items to keep", "Pinned items don't count", (1.0, 1000.0, 1.0),
+        config.history.max_items as f64, move |v| s.update(|c| c.history.max_items = v as usize)));
+    let s = settings.clone();
+    history.add(&spin_row("Largest text (MB)", "", (1.0, 64.0, 1.0),
+        config.history.max_text_size_mb as f64, move |v| s.update(|c| c.history.max_text_size_mb = v as usize)));
+    let s = settings.clone();
+    history.add(&spin_row("Largest image (MB)", "", (1.0, 500.0, 1.0),
+        config.history.max_image_size_mb as f64, move |v| s.update(|c| c.history.max_image_size_mb = v as usize)));
+    let s = settings.clone();
+    history.add(&spin_row("Thumbnail size", "Applies to newly copied images", (32.0, 256.0, 8.0),
+        config.history.thumbnail_size as f64, move |v| s.update(|c| c.history.thumbnail_size = v as u32)));
+    let s = settings.clone();
+    history.add(&switch_row("Remember source application", "Application and window title of each copy (X11 only)",
+        config.history.record_source_app, move |v| s.update(|c| c.history.record_source_app = v)));
+    page.add(&history);
+
+    let cleanup = adw::PreferencesGroup::builder()
+        .title("Cleanup")
+        .description("Pinned items are always kept. 0 turns a limit off.")
+        .build();
+    let s = settings.clone();
+    cleanup.add(&spin_row("Text items to keep", "", (0.0, 1000.0, 1.0),
+        config.history.max_text_items as f64, move |v| s.update(|c| c.history.max_text_items = v as usize)));
+    let s = settings.clone();
+    cleanup.add(&spin_row("Images to keep", "", (0.0, 1000.0, 1.0),
+        config.history.max_image_items as f64, move |v| s.update(|c| c.history.max_image_items = v as usize)));
+    let s = settings.clone();
+    cleanup.add(&spin_row("Forget after (days)", "", (0.0, 3650.0, 1.0),
+        config.history.max_age_days as f64, move |v| s.update(|c| c.history.max_age_days = v as u64)));
+    let s = settings.clone();
+    cleanup.add(&spin_row("Total size (MB)", "The largest images are removed first", (0.0, 10000.0, 10.0),
+        config.history.max_total_size_mb as f64, move |v| s.update(|c| c.history.max_total_size_mb = v as usize)));
+    page.add(&cleanup);
+
+    let window = adw::PreferencesGroup::builder().title("Popup").build();
+    let s = settings.clone();
+    window.add(&spin_row("Width", "", (300.0, 1200.0, 10.0),
+        config.window.width as f64, move |v| s.update(|c| c.window.width = v as i32)));
+    let s = settings.clone();
+    window.add(&spin_row("Height", "", (300.0, 1600.0, 10.0),
+        config.window.height as f64, move |v| s.update(|c| c.window.height = v as i32)));
+    let s = settings.clone();
+    window.add(&switch_row("Show text preview", "Toggle in the popup with Ctrl+Space", config.window.preview,
+        move |v| s.update(|c| c.window.preview = v)));
+    let s = settings.clone();
+    window.add(&spin_row("Preview width", "", (200.0, 1200.0, 10.0),
+        config.window.preview_width as f64, move |v| s.update(|c| c.window.preview_width = v as i32)));
+    page.add(&window);
+
+    let primary = adw::PreferencesGroup::builder()
+        .title("Primary Selection")
+        .description("Text selected with the mouse, pasted with a middle click")
+        .build();
+    let s = settings.clone();
+    primary.add(&switch_row("Record the primary selection", "", config.primary.capture,
+        move |v| s.update(|c| c.primary.capture = v)));
+    let s = settings.clone();
+    let mode = match config.primary.mode {
+        PrimaryMode::Separate => 0,
+        PrimaryMode::Merged => 1,
+    };
+    primary.add(&combo_row("Show in", &["Separate history (is:primary)", "Main history"], mode, move |i| {
+        s.update(|c| c.primary.mode = if i == 0 { PrimaryMode::Separate } else { PrimaryMode::Merged })
+    }));
+    page.add(&primary);
+
+    let paste = adw::PreferencesGroup::builder()
+        .title("Pasting")
+        .description("On Wayland this needs wtype or ydotool")
+        .build();
+    let s = settings.clone();
+    paste.add(&switch_row("Paste automatically", "Into the window that was focused before the popup",
+        config.paste.auto_paste, move |v| s.update(|c| c.paste.auto_paste = v)));
+    let s = settings.clone();
+    let key = match config.paste.key {
+        PasteKey::CtrlV => 0,
+        PasteKey::ShiftInsert => 1,
+        PasteKey::CtrlShiftV => 2,
+    };
+    paste.add(&combo_row("Paste with", &["Ctrl+V", "Shift+Insert", "Ctrl+Shift+V (terminals)"], key, move |i| {
+        s.update(|c| c.paste.key = match i {
+            0 => PasteKey::CtrlV,
+            1 => PasteKey::ShiftInsert,
+            _ => PasteKey::CtrlShiftV,
+        })
+    }));
+    let s = settings.clone();
+    paste.add(&spin_row("Delay (ms)", "Raise it if the paste lands before the window has focus", (0.0, 2000.0, 10.0),
+        config.paste.delay_ms as f64, move |v| s.update(|c| c.paste.delay_ms = v as u64)));
+    page.add(&paste);
+
+    let watcher = adw::PreferencesGroup::builder().title("Clipboard Watcher").build();
+    let s = settings.clone();
+    watcher.add(&spin_row(
+        "Polling interval (ms)",
+        "Only used when the desktop doesn't report clipboard changes",
+        (10.0, 5000.0, 10.0),
+        config.watcher.poll_interval_ms as f64,
+        move |v| s.update(|c| c.watcher.poll_interval_ms = v as u64),
+    ));
+    page.add(&watcher);
+
+    page
+}
+
+fn privacy_page(settings: &Settings, config: &Config) -> adw::PreferencesPage {
+    let page = adw::PreferencesPage::builder()
+        .title("Privacy")
+        .icon_name("security-high-symbolic")
+        .build();
+
+    let sensitive = adw::PreferencesGroup::builder()
+        .title("Sensitive Content")
+        .description("Passwords, keys and card numbers are never written to disk")
+        .build();
+    let s = settings.clone();
+    let action = match config.sensitive.action {
+        SensitiveAction::Skip => 0,
+        SensitiveAction::Memory => 1,
+    };
+    sensitive.add(&combo_row("When detected", &["Don't record", "Keep in memory for a while"], action, move |i| {
+        s.update(|c| c.sensitive.action = if i == 0 { SensitiveAction::Skip } else { SensitiveAction::Memory })
+    }));
+    let s = settings.clone();
+    sensitive.add(&spin_row("Forget after (seconds)", "", (5.0, 3600.0, 5.0),
+        config.sensitive.expire_seconds as f64, move |v| s.update(|c| c.sensitive.expire_seconds = v as u64)));
+    let s = settings.clone();
+    sensitive.add(&switch_row("Honor password manager hints", "KeePassXC, KWallet and others mark their copies",
+        config.sensitive.honor_hints, move |v| s.update(|c| c.sensitive.honor_hints = v)));
+    let s = settings.clone();
+    sensitive.add(&switch_row("Detect API keys and tokens", "", config.sensitive.detect_api_keys,
+        move |v| s.update(|c| c.sensitive.detect_api_keys = v)));
+    let s = settings.clone();
+    sensitive.add(&switch_row("Detect credit card numbers", "", config.sensitive.detect_credit_cards,
+        move |v| s.update(|c| c.sensitive.detect_credit_cards = v)));
+    let s = settings.clone();
+    sensitive.add(&switch_row("Detect private keys", "", config.sensitive.detect_private_keys,
+        move |v| s.update(|c| c.sensitive.detect_private_keys = v)));
+    page.add(&sensitive);
+
+    let encryption = adw::PreferencesGroup::builder()
+        .title("Encryption")
+        .description("Takes effect after a restart")
+        .build();
+    let s = settings.clone();
+    encryption.add(&switch_row("Encrypt the history file", "", config.encryption.enabled,
+        move |v| s.update(|c| c.encryption.enabled = v)));
+    let s = settings.clone();
+    let source = match config.encryption.key_source {
+        KeySource::Keyring => 0,
+        KeySource::Passphrase => 1,
+    };
+    let passphrase = format!("Passphrase from ${}", PASSPHRASE_ENV);
+    encryption.add(&combo_row("Key", &["Stored in the keyring", &passphrase], source, move |i| {
+        s.update(|c| c.encryption.key_source = if i == 0 { KeySource::Keyring } else { KeySource::Passphrase })
+    }));
+    page.add(&encryption);
+
+    page
+}
+
+fn switch_row(title: &str, subtitle: &str, active: bool, on_change: impl Fn(bool) + 'static) -> adw::SwitchRow {
+    let row = adw::SwitchRow::builder().title(title).subtitle(subtitle).active(active).build();
+    row.connect_active_notify(move |row| on_change(row.is_active()));
+    row
+}
+
+fn spin_row(
+    title: &str,
+    subtitle: &str,
+    (min, max, step): (f64, f64, f64),
+    value: f64,
+    on_change: impl Fn(f64) + 'static,
+) -> adw::SpinRow {
+    let row = adw::SpinRow::with_range(min, max, step);
+    row.set_title(title);
+    row.set_subtitle(subtitle);
+    row.set_value(value);
+    row.connect_value_notify(move |row| on_change(row.value()));
+    row
+}
+
+fn combo_row(title: &str, options: &[&str], selected: u32, on_change: impl Fn(u32) + 'static) -> adw::ComboRow {
+    let row = adw::ComboRow::builder()
+        .title(title)
+        .model(&gtk4::StringList::new(options))
+        .selected(selected)
+        .build();
+    row.connect_selected_notify(move |row| on_change(row.selected()));
+    row
+}
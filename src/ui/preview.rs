@@ -0,0 +1,141 @@
+use crate::models::{ClipboardContent, ClipboardItem};
+use gtk4::prelude::*;
+use gtk4::{gio, Box, Label, Orientation, PolicyType, ScrolledWindow, WrapMode};
+use libadwaita as adw;
+use sourceview5::prelude::*;
+
+// Highlighting very long texts makes the popup sluggish
+const MAX_HIGHLIGHT_BYTES: usize = 512 * 1024;
+
+// Enough of the text to guess its type from
+const GUESS_SAMPLE_BYTES: usize = 4096;
+
+/// Side panel with the full text of the selected item
+pub struct TextPreview {
+    root: Box,
+    buffer: sourceview5::Buffer,
+    stats: Label,
+}
+
+impl TextPreview {
+    pub fn new() -> Self {
+        let buffer = sourceview5::Buffer::new(None);
+        let view = sourceview5::View::with_buffer(&buffer);
+        view.set_show_line_numbers(true);
+        view.set_monospace(true);
+        view.set_editable(false);
+        view.set_cursor_visible(false);
+        view.set_wrap_mode(WrapMode::WordChar);
+        view.add_css_class("preview-text");
+
+        // Follow the light/dark preference like the rest of the popup
+        let style_manager = adw::StyleManager::default();
+        apply_scheme(&buffer, style_manager.is_dark());
+        let buffer_scheme = buffer.clone();
+        style_manager.connect_dark_notify(move |manager| apply_scheme(&buffer_scheme, manager.is_dark()));
+
+        let scrolled = ScrolledWindow::builder()
+            .hscrollbar_policy(PolicyType::Never)
+            .vscrollbar_policy(PolicyType::Automatic)
+            .vexpand(true)
+            .child(&view)
+            .build();
+
+        let stats = Label::new(None);
+        stats.set_xalign(0.0);
+        stats.add_css_class("timestamp");
+
+        let root = Box::new(Orientation::Vertical, 4);
+        root.add_css_class("preview-pane");
+        root.set_hexpand(true);
+        root.append(&scrolled);
+        root.append(&stats);
+
+        Self { root, buffer, stats }
+    }
+
+    pub fn widget(&self) -> &Box {
+        &self.root
+    }
+
+    /// Show `item`, or clear the pane when nothing is selected
+    pub fn show_item(&self, item: Option<&ClipboardItem>) {
+        let (text, language) = match item.map(|item| (item, &item.content)) {
+            None => (String::new(), None),
+            // Never show secrets, only that something is there
+            Some((item, _)) if item.is_sensitive() => ("••••••••".to_string(), None),
+            Some((_, ClipboardContent::Text(text))) => (text.clone(), guess_language(text)),
+            Some((_, ClipboardContent::Html { html, .. })) => (html.clone(), language("html")),
+            Some((_, ClipboardContent::Rtf { text, .. })) => (text.clone(), None),
+            Some((_, ClipboardContent::Files(uris))) => {
+                let paths: Vec<String> = uris.iter().map(|uri| crate::mime::uri_to_path(uri)).collect();
+                (paths.join("\n"), None)
+            }
+            Some((_, ClipboardContent::Image { width, height, .. })) => {
+                self.buffer.set_language(None);
+                self.buffer.set_text("");
+                self.stats.set_text(&format!("Image {}×{}, Ctrl+O to view", width, height));
+                return;
+            }
+        };
+
+        self.buffer.set_highlight_syntax(text.len() <= MAX_HIGHLIGHT_BYTES);
+        self.buffer.set_language(language.as_ref());
+        self.buffer.set_text(&text);
+
+        let stats = match item {
+            Some(item) if item.truncated => format!("First {} KiB of {} KiB", text.len() / 1024, item.size / 1024),
+            Some(item) if !item.is_sensitive() => text_stats(&text),
+            _ => String::new(),
+        };
+        self.stats.set_text(&stats);
+    }
+}
+
+pub fn apply_scheme(buffer: &sourceview5::Buffer, dark: bool) {
+    let name = if dark { "Adwaita-dark" } else { "Adwaita" };
+    buffer.set_style_scheme(sourceview5::StyleSchemeManager::default().scheme(name).as_ref());
+}
+
+fn language(id: &str) -> Option<sourceview5::Language> {
+    sourceview5::LanguageManager::default().language(id)
+}
+
+/// Language of copied text. Formats GIO doesn't recognize from content alone
+/// are checked first, the rest goes by the guessed content type (shebangs,
+/// XML declarations and the like).
+pub fn guess_language(text: &str) -> Option<sourceview5::Language> {
+    let trimmed = text.trim_start();
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(text).is_ok()
+    {
+        return language("json");
+    }
+    if trimmed.starts_with("diff --git") || (trimmed.starts_with("--- ") && text.contains("\n+++ ")) {
+        return language("diff");
+    }
+
+    let mut sample_end = text.len().min(GUESS_SAMPLE_BYTES);
+    while !text.is_char_boundary(sample_end) {
+        sample_end -= 1;
+    }
+    let (content_type, _) = gio::content_type_guess(None::<&str>, &text.as_bytes()[..sample_end]);
+    if content_type == "text/plain" {
+        return None;
+    }
+    sourceview5::LanguageManager::default().guess_language(None::<&str>, Some(&content_type))
+}
+
+fn text_stats(text: &str) -> String {
+    let characters = text.chars().count();
+    let lines = text.lines().count();
+    let words = text.split_whitespace().count();
+    format!(
+        "{} character{} · {} line{} · {} word{}",
+        characters, plural(characters), lines, plural(lines), words, plural(words),
+    )
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
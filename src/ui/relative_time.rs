@@ -0,0 +1,32 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+
+/// Time of an item for its row: "just now", "5 min ago", "3 h ago",
+/// "yesterday, 14:05", or the time of day for older items (the day header
+/// above tells the date)
+pub fn relative_time(timestamp: DateTime<Utc>, now: DateTime<Local>) -> String {
+    let local = timestamp.with_timezone(&Local);
+    let minutes = now.signed_duration_since(local).num_minutes();
+    match (now.date_naive() - local.date_naive()).num_days() {
+        _ if minutes < 1 => "just now".to_string(),
+        _ if minutes < 60 => format!("{} min ago", minutes),
+        0 => format!("{} h ago", minutes / 60),
+        1 => format!("yesterday, {}", local.format("%H:%M")),
+        _ => local.format("%H:%M").to_string(),
+    }
+}
+
+/// Header for the items copied on `day`
+pub fn day_header(day: NaiveDate, today: NaiveDate) -> String {
+    match (today - day).num_days() {
+        0 => "Today".to_string(),
+        1 => "Yesterday".to_string(),
+        2..=6 => day.format("%A").to_string(),
+        _ if day.year() == today.year() => day.format("%A, %-d %B").to_string(),
+        _ => day.format("%-d %B %Y").to_string(),
+    }
+}
+
+/// Local calendar day of a timestamp
+pub fn local_day(timestamp: DateTime<Utc>) -> NaiveDate {
+    timestamp.with_timezone(&Local).date_naive()
+}
@@ -14,6 +14,15 @@ pub fn apply_styles() {
             background: transparent;
         }
         
+        .search-entry {
+            margin: 4px 4px 8px 4px;
+        }
+        
+        .placeholder {
+            margin: 24px;
+            opacity: 0.5;
+        }
+        
         .clipboard-item {
             padding: 6px;
             margin: 2px 4px; 
@@ -54,11 +63,50 @@ pub fn apply_styles() {
             color: @theme_fg_color;
         }
         
+        .preview-pane {
+            margin: 2px 4px;
+        }
+        
+        .preview-text {
+            font-size: 12px;
+            border-radius: 6px;
+            padding: 6px;
+        }
+        
+        .day-header {
+            font-size: 11px;
+            font-weight: bold;
+            opacity: 0.6;
+            margin: 10px 12px 2px 12px;
+        }
+        
         .timestamp {
             font-size: 11px;
             opacity: 0.5;
         }
         
+        .format-badge {
+            font-size: 10px;
+            font-weight: bold;
+            padding: 0 4px;
+            border-radius: 4px;
+            background: alpha(@theme_selected_bg_color, 0.15);
+            color: @theme_selected_bg_color;
+        }
+        
+        .color-swatch {
+            border-radius: 4px;
+            border: 1px solid alpha(@theme_fg_color, 0.2);
+        }
+        
+        .file-icon {
+            opacity: 0.7;
+        }
+        
+        .source-icon {
+            opacity: 0.6;
+        }
+        
         /* Image styles */
         .thumbnail {
             border-radius: 6px;
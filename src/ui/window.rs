@@ -1,30 +1,46 @@
-use crate::clipboard::SharedClipboardManager;
-use crate::models::ClipboardItem;
-use crate::ui::list_item::create_list_row;
+use crate::clipboard::AddOutcome;
+use crate::color::ColorFormat;
+use crate::config::{Config, PrimaryMode, WindowConfig};
+use crate::daemon::DaemonClient;
+use crate::models::{ClipboardItem, Selection};
+use crate::autopaste;
+use crate::search::SearchQuery;
+use crate::storage::RESIDENT_TEXT;
+use crate::transform::Transform;
+use crate::ui::editor;
+use crate::ui::image_viewer;
+use crate::ui::item_object::{sync_store, ItemObject};
+use crate::ui::list_item::{create_list_row, EDIT_ACTION, VIEW_IMAGE_ACTION};
+use crate::ui::preferences;
+use crate::ui::preview::TextPreview;
+use crate::ui::relative_time::{day_header, local_day};
 use crate::ui::styles::apply_styles;
+use chrono::{Local, NaiveDate};
 use gtk4::prelude::*;
 use gtk4::{
-    glib, Box, ListBox, Orientation, ScrolledWindow, 
-    SelectionMode, PolicyType, EventControllerKey, gdk,
+    gio, glib, Box, Orientation, ScrolledWindow, PolicyType, EventControllerKey, gdk,
+    Label, SearchEntry, PropagationPhase, CustomFilter, CustomSorter, FilterChange,
+    FilterListModel, ListHeader, ListItem, ListScrollFlags, ListView, SignalListItemFactory,
+    SingleSelection, SortListModel, SorterChange,
 };
 use libadwaita as adw;
 use libadwaita::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::rc::Rc;
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
-use crossbeam_channel::{bounded, Sender, Receiver};
 
-enum UiMessage {
-    ItemsChanged(Vec<ClipboardItem>),
-}
+// Rows moved by PageUp/PageDown
+const PAGE_STEP: i32 = 5;
 
-pub fn build_ui(app: &adw::Application) -> adw::ApplicationWindow {
-    let manager = SharedClipboardManager::new();
+pub fn build_ui(app: &adw::Application, client: Arc<DaemonClient>) -> adw::ApplicationWindow {
 
+    let config = Config::load();
     let window = adw::ApplicationWindow::builder()
         .application(app)
-        .default_width(450)
-        .default_height(600)
+        .default_width(config.window.total_width())
+        .default_height(config.window.height)
         .decorated(false)
         .resizable(false)
         .build();
@@ -43,60 +59,358 @@ pub fn build_ui(app: &adw::Application) -> adw::ApplicationWindow {
     main_box.set_margin_start(6);
     main_box.set_margin_end(6);
 
+    let search_entry = SearchEntry::new();
+    search_entry.set_placeholder_text(Some("Search (is:text, is:url, is:code, is:image, is:pinned, app:firefox…)"));
+    search_entry.set_tooltip_text(Some(&format!(
+        "Words match anywhere in an item, or its letters in order. Long items are searched in their first {} KiB.",
+        RESIDENT_TEXT / 1024,
+    )));
+    search_entry.add_css_class("search-entry");
+    main_box.append(&search_entry);
+
     let scrolled_window = ScrolledWindow::builder()
         .hscrollbar_policy(PolicyType::Never)
         .vscrollbar_policy(PolicyType::Automatic)
         .vexpand(true)
         .build();
 
-    let list_box = ListBox::new();
-    list_box.set_selection_mode(SelectionMode::None);
-    list_box.add_css_class("popup-list");
-    scrolled_window.set_child(Some(&list_box));
+    // The history is kept in the store and updated in place, so only rows
+    // of changed items are rebuilt. Search filters and sorts on top of it.
+    let store = gio::ListStore::new::<ItemObject>();
+    let search = ListSearch::new(config.primary.mode == PrimaryMode::Separate);
+    let filtered = FilterListModel::new(Some(store.clone()), Some(search.filter.clone()));
+    let sorted = SortListModel::new(Some(filtered), Some(search.sorter.clone()));
+    let selection = SingleSelection::new(Some(sorted.clone()));
+
+    let factory = SignalListItemFactory::new();
+    factory.connect_bind(|_, list_item| {
+        let list_item = list_item.downcast_ref::<ListItem>().expect("list view items");
+        if let Some(obj) = list_item.item().and_downcast::<ItemObject>() {
+            list_item.set_child(Some(&create_list_row(&obj)));
+        }
+    });
+    factory.connect_unbind(|_, list_item| {
+        let list_item = list_item.downcast_ref::<ListItem>().expect("list view items");
+        list_item.set_child(gtk4::Widget::NONE);
+    });
+
+    let headers = SignalListItemFactory::new();
+    headers.connect_setup(|_, header| {
+        let header = header.downcast_ref::<ListHeader>().expect("list view headers");
+        let label = Label::new(None);
+        label.set_xalign(0.0);
+        label.add_css_class("day-header");
+        header.set_child(Some(&label));
+    });
+    headers.connect_bind(|_, header| {
+        let header = header.downcast_ref::<ListHeader>().expect("list view headers");
+        let (Some(obj), Some(label)) = (
+            header.item().and_downcast::<ItemObject>(),
+            header.child().and_downcast::<Label>(),
+        ) else {
+            return;
+        };
+        let item = obj.item();
+        if item.pinned {
+            label.set_text("Pinned");
+        } else {
+            label.set_text(&day_header(local_day(item.timestamp), Local::now().date_naive()));
+        }
+    });
+
+    let list_view = ListView::new(Some(selection.clone()), Some(factory));
+    let sections = DaySections {
+        model: sorted,
+        list_view: list_view.clone(),
+        sorter: CustomSorter::new(|a, b| section_key(a).cmp(&section_key(b)).into()),
+        headers,
+    };
+    sections.follow(&search);
+    list_view.set_single_click_activate(true);
+    list_view.add_css_class("popup-list");
+    scrolled_window.set_child(Some(&list_view));
+
+    let placeholder = Label::new(Some("No matching items"));
+    placeholder.add_css_class("placeholder");
+    placeholder.set_vexpand(true);
+    let scrolled_placeholder = scrolled_window.clone();
+    let placeholder_changes = placeholder.clone();
+    selection.connect_items_changed(move |model, _, _, _| {
+        let empty = model.n_items() == 0;
+        placeholder_changes.set_visible(empty);
+        scrolled_placeholder.set_visible(!empty);
+    });
 
     // Initial load
-    {
-        let mgr = manager.0.read();
-        refresh_list(mgr.get_items(), &list_box);
-    }
+    sync_store(&store, fetch_items(&client));
+    placeholder.set_visible(selection.n_items() == 0);
+    scrolled_window.set_visible(selection.n_items() > 0);
+    select_index(&list_view, 0);
+
+    // Start every popup with an empty search. Fetched again in case the
+    // daemon was restarted while hidden.
+    let client_show = Arc::clone(&client);
+    let store_show = store.clone();
+    let list_view_show = list_view.clone();
+    let search_show = search_entry.clone();
+    let paste_target = PasteTarget::default();
+    let paste_target_show = paste_target.clone();
+    window.connect_show(move |_| {
+        // Before the popup takes the focus
+        paste_target_show.remember_focus();
+        search_show.set_text("");
+        search_show.grab_focus();
+        sync_store(&store_show, fetch_items(&client_show));
+        select_index(&list_view_show, 0);
+    });
+
+    // Search as you type
+    let search_changed = search.clone();
+    let sections_search = sections.clone();
+    let list_view_search = list_view.clone();
+    search_entry.connect_search_changed(move |entry| {
+        search_changed.set_text(&entry.text());
+        sections_search.follow(&search_changed);
+        select_index(&list_view_search, 0);
+    });
+
+    search_entry.set_key_capture_widget(Some(&window));
 
     // Click handling
     let window_clone = window.clone();
-    let manager_click = Arc::clone(&manager);
-    
-    list_box.connect_row_activated(move |list, row| {
-        let item_id = unsafe { row.data::<String>("item_id") };
-        
-        if let Some(id_ptr) = item_id {
-            let id_str = unsafe { id_ptr.as_ref() }.to_string();
-            let is_pin_click = unsafe { row.data::<bool>("is_pin_click") }.is_some();
-
-            if is_pin_click {
-                let mut mgr = manager_click.0.write();
-                mgr.toggle_pin(&id_str);
-                refresh_list(mgr.get_items(), list);
-            } else {
-                let mut mgr = manager_click.0.write();
-                let _ = mgr.paste_item(&id_str);
-                drop(mgr);
-                window_clone.set_visible(false); 
+    let client_click = Arc::clone(&client);
+    let paste_target_click = paste_target.clone();
+    list_view.connect_activate(move |view, position| {
+        if let Some(id) = item_id_at(view, position) {
+            paste_and_hide(&client_click, &window_clone, &paste_target_click, &id, Selection::Clipboard);
+        }
+    });
+
+    // Shows errors of the row actions below
+    let toast_overlay = adw::ToastOverlay::new();
+
+    // Buttons and menus in the rows
+    let actions = gio::SimpleActionGroup::new();
+    let toggle_pin = gio::SimpleAction::new("toggle-pin", Some(glib::VariantTy::STRING));
+    let client_pin = Arc::clone(&client);
+    let store_pin = store.clone();
+    toggle_pin.connect_activate(move |_, parameter| {
+        if let Some(id) = parameter.and_then(|p| p.get::<String>()) {
+            if let Err(e) = client_pin.toggle_pin(&id) {
+                eprintln!("Failed to pin item: {}", e);
+            }
+            sync_store(&store_pin, fetch_items(&client_pin));
+        }
+    });
+    actions.add_action(&toggle_pin);
+
+    let view_image = gio::SimpleAction::new("view-image", Some(glib::VariantTy::STRING));
+    let client_view = Arc::clone(&client);
+    let store_view = store.clone();
+    let window_view = window.clone();
+    let app_view = app.clone();
+    view_image.connect_activate(move |_, parameter| {
+        let Some(id) = parameter.and_then(|p| p.get::<String>()) else {
+            return;
+        };
+        if let Some(obj) = find_object(&store_view, &id) {
+            // The viewer takes focus, which would hide the popup anyway
+            window_view.set_visible(false);
+            image_viewer::show(&app_view, &client_view, &obj.item());
+        }
+    });
+    actions.add_action(&view_image);
+
+    // Adds the converted color to the history and pastes it from there
+    let copy_color = gio::SimpleAction::new("copy-color", Some(&glib::VariantType::new("(ss)").expect("valid type")));
+    let client_color = Arc::clone(&client);
+    let store_color = store.clone();
+    let window_color = window.clone();
+    let paste_target_color = paste_target.clone();
+    copy_color.connect_activate(move |_, parameter| {
+        let Some((id, format)) = parameter.and_then(|p| p.get::<(String, String)>()) else {
+            return;
+        };
+        let color = find_object(&store_color, &id).and_then(|obj| obj.item().color);
+        let (Some(color), Some(format)) = (color, ColorFormat::from_name(&format)) else {
+            return;
+        };
+        match client_color.add_text(color.format(format), false) {
+            Ok(AddOutcome::Added(new_id) | AddOutcome::Unchanged(new_id)) => {
+                paste_and_hide(&client_color, &window_color, &paste_target_color, &new_id, Selection::Clipboard)
+            }
+            Ok(AddOutcome::Ignored) => eprintln!("Failed to copy color: not recorded"),
+            Err(e) => eprintln!("Failed to copy color: {}", e),
+        }
+    });
+    actions.add_action(&copy_color);
+
+    let paste_transformed = gio::SimpleAction::new(
+        "paste-transformed",
+        Some(&glib::VariantType::new("(ss)").expect("valid type")),
+    );
+    let client_transform = Arc::clone(&client);
+    let window_transform = window.clone();
+    let paste_target_transform = paste_target.clone();
+    let toasts_transform = toast_overlay.clone();
+    paste_transformed.connect_activate(move |_, parameter| {
+        let Some((id, name)) = parameter.and_then(|p| p.get::<(String, String)>()) else {
+            return;
+        };
+        let Some(transform) = Transform::from_name(&name) else {
+            return;
+        };
+        match client_transform.paste_transformed(&id, Selection::Clipboard, transform) {
+            Ok(()) => {
+                window_transform.set_visible(false);
+                paste_target_transform.paste_after_hide();
             }
+            // Stay open so it's clear nothing was pasted
+            Err(e) => toasts_transform.add_toast(adw::Toast::new(&format!("{}: {}", transform.label(), e))),
+        }
+    });
+    actions.add_action(&paste_transformed);
+
+    let edit = gio::SimpleAction::new("edit", Some(glib::VariantTy::STRING));
+    let client_edit = Arc::clone(&client);
+    let store_edit = store.clone();
+    let window_edit = window.clone();
+    let app_edit = app.clone();
+    edit.connect_activate(move |_, parameter| {
+        let Some(id) = parameter.and_then(|p| p.get::<String>()) else {
+            return;
+        };
+        if let Some(obj) = find_object(&store_edit, &id) {
+            window_edit.set_visible(false);
+            editor::show(&app_edit, Arc::clone(&client_edit), &obj.item());
         }
     });
+    actions.add_action(&edit);
+    window.insert_action_group("popup", Some(&actions));
+
+    let list_column = Box::new(Orientation::Vertical, 0);
+    list_column.append(&scrolled_window);
+    list_column.append(&placeholder);
+
+    let preview = PreviewPane {
+        text: Rc::new(TextPreview::new()),
+        list_column: list_column.clone(),
+        selection: selection.clone(),
+        config: Rc::new(RefCell::new(config.window.clone())),
+    };
+    let columns = Box::new(Orientation::Horizontal, 6);
+    columns.set_vexpand(true);
+    columns.append(&list_column);
+    columns.append(preview.text.widget());
+    main_box.append(&columns);
+    preview.apply(&window, &config.window);
 
-    main_box.append(&scrolled_window);
-    window.set_content(Some(&main_box));
+    let preview_selection = preview.clone();
+    selection.connect_selected_item_notify(move |_| preview_selection.refresh());
 
-    // Keyboard handling
+    toast_overlay.set_child(Some(&main_box));
+    window.set_content(Some(&toast_overlay));
+
+    if let Ok(Some(notice)) = client.take_storage_notice() {
+        let toast = adw::Toast::new(&notice);
+        // Stays until dismissed, the popup may not be looked at right away
+        toast.set_timeout(0);
+        toast_overlay.add_toast(toast);
+    }
+
+    // Keyboard handling. Runs in the capture phase so navigation keeps
+    // working while the search entry has focus.
     let key_controller = EventControllerKey::new();
+    key_controller.set_propagation_phase(PropagationPhase::Capture);
     let window_clone = window.clone();
-    key_controller.connect_key_pressed(move |_, key, _, _| {
-        if key == gdk::Key::Escape {
-            window_clone.set_visible(false);
-            glib::Propagation::Stop
-        } else {
-            glib::Propagation::Proceed
+    let client_keys = Arc::clone(&client);
+    let store_keys = store.clone();
+    let app_keys = app.clone();
+    let list_view_keys = list_view.clone();
+    let search_keys = search_entry.clone();
+    let paste_target_keys = paste_target.clone();
+    let preview_keys = preview.clone();
+    key_controller.connect_key_pressed(move |_, key, _, modifiers| {
+        let list = &list_view_keys;
+        let ctrl = modifiers.contains(gdk::ModifierType::CONTROL_MASK);
+        let shift = modifiers.contains(gdk::ModifierType::SHIFT_MASK);
+        let alt = modifiers.contains(gdk::ModifierType::ALT_MASK);
+        let selected = selected_index(list);
+        let last = row_count(list) - 1;
+
+        match key {
+            gdk::Key::Escape => {
+                window_clone.set_visible(false);
+            }
+            gdk::Key::Up | gdk::Key::KP_Up => select_index(list, selected - 1),
+            gdk::Key::Down | gdk::Key::KP_Down => select_index(list, selected + 1),
+            gdk::Key::Page_Up | gdk::Key::KP_Page_Up => select_index(list, selected - PAGE_STEP),
+            gdk::Key::Page_Down | gdk::Key::KP_Page_Down => select_index(list, selected + PAGE_STEP),
+            gdk::Key::Home | gdk::Key::KP_Home => select_index(list, 0),
+            gdk::Key::End | gdk::Key::KP_End => select_index(list, last),
+            // Shift+Enter pastes into the primary selection instead
+            gdk::Key::Return | gdk::Key::KP_Enter | gdk::Key::ISO_Enter => {
+                let target = if shift { Selection::Primary } else { Selection::Clipboard };
+                if let Some(id) = item_id_at(list, selected as u32) {
+                    paste_and_hide(&client_keys, &window_clone, &paste_target_keys, &id, target);
+                }
+            }
+            // Forward-delete is a no-op at the end of the search text, so only
+            // take Delete over there
+            gdk::Key::Delete | gdk::Key::KP_Delete
+                if search_keys.position() as usize >= search_keys.text().chars().count() =>
+            {
+                let Some(id) = item_id_at(list, selected as u32) else {
+                    return glib::Propagation::Stop;
+                };
+                if let Err(e) = client_keys.delete_item(&id) {
+                    eprintln!("Failed to delete item: {}", e);
+                }
+                sync_store(&store_keys, fetch_items(&client_keys));
+                select_index(list, selected.min(row_count(list) - 1));
+            }
+            gdk::Key::o | gdk::Key::O if ctrl => {
+                if let Some(id) = item_id_at(list, selected as u32) {
+                    let _ = WidgetExt::activate_action(&window_clone, VIEW_IMAGE_ACTION, Some(&id.to_variant()));
+                }
+            }
+            // Until the popup is restarted or the config file changes
+            gdk::Key::space if ctrl => preview_keys.toggle(&window_clone),
+            gdk::Key::e | gdk::Key::E if ctrl => {
+                if let Some(id) = item_id_at(list, selected as u32) {
+                    let _ = WidgetExt::activate_action(&window_clone, EDIT_ACTION, Some(&id.to_variant()));
+                }
+            }
+            gdk::Key::comma if ctrl => {
+                window_clone.set_visible(false);
+                preferences::show(&app_keys);
+            }
+            gdk::Key::p | gdk::Key::P if ctrl => {
+                let Some(id) = item_id_at(list, selected as u32) else {
+                    return glib::Propagation::Stop;
+                };
+                if let Err(e) = client_keys.toggle_pin(&id) {
+                    eprintln!("Failed to pin item: {}", e);
+                }
+                sync_store(&store_keys, fetch_items(&client_keys));
+                select_item(list, &id);
+            }
+            _ => {
+                // Alt+1..9 pastes the Nth visible item
+                let digit = key.to_unicode()
+                    .and_then(|c| c.to_digit(10))
+                    .filter(|d| (1..=9).contains(d));
+                match digit {
+                    Some(n) if alt => {
+                        if let Some(id) = item_id_at(list, n - 1) {
+                            paste_and_hide(&client_keys, &window_clone, &paste_target_keys, &id, Selection::Clipboard);
+                        }
+                    }
+                    _ => return glib::Propagation::Proceed,
+                }
+            }
         }
+        glib::Propagation::Stop
     });
     window.add_controller(key_controller);
 
@@ -106,62 +420,305 @@ pub fn build_ui(app: &adw::Application) -> adw::ApplicationWindow {
         }
     });
 
-    // Background polling
-    let (sender, receiver): (Sender<UiMessage>, Receiver<UiMessage>) = bounded(4);
-    let manager_thread = Arc::clone(&manager);
+    // Follow changes recorded by the daemon
+    let (sender, receiver) = async_channel::unbounded();
+    match client.subscribe() {
+        Ok(events) => {
+            thread::spawn(move || {
+                while events.recv().is_ok() {
+                    // One refresh for a burst of changes
+                    events.try_iter().for_each(drop);
+                    if sender.send_blocking(()).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        Err(e) => eprintln!("History changes won't show until the popup is reopened: {}", e),
+    }
 
-    thread::spawn(move || {
-        let mut refresh_counter = 0u32;
-        
-        loop {
-            // 25ms polling - fast but not too aggressive
-            thread::sleep(Duration::from_millis(25));
-            
-            let mut mgr = manager_thread.0.write();
-            
-            if mgr.check_clipboard_fast() {
-                let items = mgr.get_items().to_vec();
-                let _ = sender.try_send(UiMessage::ItemsChanged(items));
-            }
-            
-            // Refresh clipboard connection every ~5 seconds
-            refresh_counter += 1;
-            if refresh_counter >= 200 {
-                refresh_counter = 0;
-                mgr.refresh_clipboard();
-            }
+    let client_changes = Arc::clone(&client);
+    let store_changes = store.clone();
+    glib::MainContext::default().spawn_local_with_priority(glib::Priority::HIGH, async move {
+        while receiver.recv().await.is_ok() {
+            sync_store(&store_changes, fetch_items(&client_changes));
         }
     });
 
-    // UI update receiver
-    let (glib_sender, glib_receiver) = glib::MainContext::channel(glib::Priority::HIGH);
-    
-    thread::spawn(move || {
-        while let Ok(msg) = receiver.recv() {
-            let _ = glib_sender.send(msg);
+    if let Some(monitor) = watch_config(&window, &search, &sections, &search_entry, &preview) {
+        // Kept alive by the handler, the monitor stops when dropped
+        window.connect_destroy(move |_| {
+            monitor.cancel();
+        });
+    }
+
+    window
+}
+
+/// Search entry text applied to the list through a filter and a sorter
+#[derive(Clone)]
+struct ListSearch {
+    query: Rc<RefCell<SearchQuery>>,
+    filter: CustomFilter,
+    sorter: CustomSorter,
+    // Cell so a reloaded config can switch it
+    separate_primary: Rc<Cell<bool>>,
+}
+
+impl ListSearch {
+    fn new(separate_primary: bool) -> Self {
+        let query = Rc::new(RefCell::new(SearchQuery::default()));
+
+        let query_filter = Rc::clone(&query);
+        let filter = CustomFilter::new(move |obj| {
+            let obj = obj.downcast_ref::<ItemObject>().expect("store only holds ItemObjects");
+            query_filter.borrow().matches(&obj.item())
+        });
+
+        let query_sorter = Rc::clone(&query);
+        let sorter = CustomSorter::new(move |a, b| {
+            let a = a.downcast_ref::<ItemObject>().expect("store only holds ItemObjects");
+            let b = b.downcast_ref::<ItemObject>().expect("store only holds ItemObjects");
+            query_sorter.borrow().compare(&a.item(), &b.item()).into()
+        });
+
+        let search = Self {
+            query,
+            filter,
+            sorter,
+            separate_primary: Rc::new(Cell::new(separate_primary)),
+        };
+        search.set_text("");
+        search
+    }
+
+    fn set_text(&self, text: &str) {
+        // Separate primary history only shows up with is:primary
+        let mut query = SearchQuery::parse(text);
+        if self.separate_primary.get() {
+            query = query.with_default_source(Selection::Clipboard);
         }
-    });
+        self.query.replace(query);
+        self.filter.changed(FilterChange::Different);
+        self.sorter.changed(SorterChange::Different);
+    }
+}
+
+/// Day headers over the list. GTK sorts by section before the search
+/// sorter, so they're left out while results are ranked.
+#[derive(Clone)]
+struct DaySections {
+    model: SortListModel,
+    list_view: ListView,
+    sorter: CustomSorter,
+    headers: SignalListItemFactory,
+}
+
+impl DaySections {
+    fn follow(&self, search: &ListSearch) {
+        let shown = !search.query.borrow().is_ranked();
+        self.model.set_section_sorter(shown.then_some(&self.sorter));
+        self.list_view.set_header_factory(shown.then_some(&self.headers));
+    }
+}
+
+/// Follow popup settings in the config file, the daemon applies the rest
+fn watch_config(
+    window: &adw::ApplicationWindow,
+    search: &ListSearch,
+    sections: &DaySections,
+    search_entry: &SearchEntry,
+    preview: &PreviewPane,
+) -> Option<gio::FileMonitor> {
+    let file = gio::File::for_path(Config::path());
+    let monitor = match file.monitor_file(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            eprintln!("Config changes won't be picked up until restart: {}", e);
+            return None;
+        }
+    };
 
-    let list_box_clone = list_box.clone();
-    glib_receiver.attach(None, move |msg| {
-        match msg {
-            UiMessage::ItemsChanged(items) => {
-                refresh_list(&items, &list_box_clone);
+    let window_weak = window.downgrade();
+    let search = search.clone();
+    let sections = sections.clone();
+    let search_entry = search_entry.clone();
+    let preview = preview.clone();
+    monitor.connect_changed(move |_, _, _, event| {
+        if !matches!(event, gio::FileMonitorEvent::ChangesDoneHint | gio::FileMonitorEvent::Created) {
+            return;
+        }
+
+        let config = match Config::try_load() {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Keeping the current settings, invalid config {}", e);
+                return;
             }
+        };
+        if let Some(window) = window_weak.upgrade() {
+            preview.apply(&window, &config.window);
         }
-        glib::ControlFlow::Continue
+
+        search.separate_primary.set(config.primary.mode == PrimaryMode::Separate);
+        search.set_text(&search_entry.text());
+        sections.follow(&search);
     });
+    Some(monitor)
+}
 
-    window
+/// The text preview next to the list
+#[derive(Clone)]
+struct PreviewPane {
+    text: Rc<TextPreview>,
+    list_column: Box,
+    selection: SingleSelection,
+    // Settings in effect, Ctrl+Space flips the preview in here only
+    config: Rc<RefCell<WindowConfig>>,
 }
 
-fn refresh_list(items: &[ClipboardItem], list_box: &ListBox) {
-    while let Some(child) = list_box.first_child() {
-        list_box.remove(&child);
+impl PreviewPane {
+    /// Show or hide the preview and size the window to match
+    fn apply(&self, window: &adw::ApplicationWindow, config: &WindowConfig) {
+        self.config.replace(config.clone());
+        self.show(window);
+    }
+
+    fn toggle(&self, window: &adw::ApplicationWindow) {
+        let preview = !self.config.borrow().preview;
+        self.config.borrow_mut().preview = preview;
+        self.show(window);
+    }
+
+    fn show(&self, window: &adw::ApplicationWindow) {
+        let config = self.config.borrow();
+        // Keep the list as wide as without the preview
+        self.list_column.set_size_request(if config.preview { config.width - 12 } else { -1 }, -1);
+        self.list_column.set_hexpand(!config.preview);
+        self.text.widget().set_visible(config.preview);
+        window.set_default_size(config.total_width(), config.height);
+        self.refresh();
+    }
+
+    fn refresh(&self) {
+        // Not kept up to date while hidden, long texts are slow to highlight
+        if !self.text.widget().is_visible() {
+            return;
+        }
+        let obj = self.selection.selected_item().and_downcast::<ItemObject>();
+        self.text.show_item(obj.as_ref().map(|obj| obj.item()).as_deref());
     }
-    
-    for item in items {
-        let row = create_list_row(item);
-        list_box.append(&row);
+}
+
+/// Sections of the list: pinned items first, then one per day, newest first
+fn section_key(obj: &glib::Object) -> (bool, Reverse<NaiveDate>) {
+    let obj = obj.downcast_ref::<ItemObject>().expect("store only holds ItemObjects");
+    let item = obj.item();
+    if item.pinned {
+        (false, Reverse(NaiveDate::MIN))
+    } else {
+        (true, Reverse(local_day(item.timestamp)))
     }
-}
\ No newline at end of file
+}
+
+fn paste_and_hide(
+    client: &DaemonClient,
+    window: &adw::ApplicationWindow,
+    paste_target: &PasteTarget,
+    id: &str,
+    target: Selection,
+) {
+    match client.paste_item(id, target) {
+        Ok(()) => {
+            window.set_visible(false);
+            // The primary selection is pasted with the mouse
+            if target == Selection::Clipboard {
+                paste_target.paste_after_hide();
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to paste: {}", e);
+            window.set_visible(false);
+        }
+    }
+}
+
+/// Window focused before the popup opened, pasted into when `[paste]
+/// auto_paste` is on
+#[derive(Clone, Default)]
+struct PasteTarget(Rc<Cell<Option<u32>>>);
+
+impl PasteTarget {
+    fn remember_focus(&self) {
+        self.0.set(autopaste::active_window());
+    }
+
+    /// Press the paste keys once the popup is gone and the focus is back
+    fn paste_after_hide(&self) {
+        let config = Config::load().paste;
+        if !config.auto_paste {
+            return;
+        }
+        let window = self.0.get();
+        glib::timeout_add_local_once(config.delay(), move || {
+            thread::spawn(move || {
+                if let Err(e) = autopaste::send_paste(config.key, window) {
+                    eprintln!("Failed to paste automatically: {}", e);
+                }
+            });
+        });
+    }
+}
+
+fn fetch_items(client: &DaemonClient) -> Vec<ClipboardItem> {
+    client.items().unwrap_or_else(|e| {
+        eprintln!("Failed to load history: {}", e);
+        Vec::new()
+    })
+}
+
+fn find_object(store: &gio::ListStore, id: &str) -> Option<ItemObject> {
+    (0..store.n_items())
+        .filter_map(|position| store.item(position).and_downcast::<ItemObject>())
+        .find(|obj| obj.id() == id)
+}
+
+fn selection_model(list_view: &ListView) -> SingleSelection {
+    list_view.model().and_downcast().expect("list view uses a SingleSelection")
+}
+
+/// Id of the item shown at `position`
+fn item_id_at(list_view: &ListView, position: u32) -> Option<String> {
+    selection_model(list_view)
+        .item(position)
+        .and_downcast::<ItemObject>()
+        .map(|obj| obj.id())
+}
+
+fn row_count(list_view: &ListView) -> i32 {
+    selection_model(list_view).n_items() as i32
+}
+
+/// Position of the selected row, -1 if there is none
+fn selected_index(list_view: &ListView) -> i32 {
+    match selection_model(list_view).selected() {
+        gtk4::INVALID_LIST_POSITION => -1,
+        position => position as i32,
+    }
+}
+
+/// Select the row at `index` (clamped to the list) and scroll it into view
+fn select_index(list_view: &ListView, index: i32) {
+    let last = row_count(list_view) - 1;
+    if last < 0 {
+        return;
+    }
+    list_view.scroll_to(index.clamp(0, last) as u32, ListScrollFlags::SELECT, None);
+}
+
+fn select_item(list_view: &ListView, id: &str) {
+    let count = row_count(list_view);
+    if let Some(index) = (0..count).find(|&i| item_id_at(list_view, i as u32).as_deref() == Some(id)) {
+        select_index(list_view, index);
+    }
+}
@@ -0,0 +1,218 @@
+// Clipboard change notifications: the wlroots data-control protocol on
+// Wayland, XFixes selection events on X11 (also covers XWayland), and
+// polling only when neither is available.
+
+use crate::models::Selection;
+use crossbeam_channel::Sender;
+use std::thread;
+use std::time::Duration;
+
+/// Spawn a thread that sends on `on_change` whenever a selection owner changes.
+/// One notification per selection is sent at startup so the current content is captured.
+/// `poll_interval` is asked on every tick, and only if polling is needed.
+pub fn spawn(on_change: Sender<Selection>, poll_interval: impl Fn() -> Duration + Send + 'static) {
+    thread::spawn(move || {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            match wayland::watch(&on_change) {
+                Ok(()) => return,
+                Err(e) => eprintln!("Wayland clipboard notifications unavailable: {}", e),
+            }
+        }
+
+        if std::env::var_os("DISPLAY").is_some() {
+            match x11::watch(&on_change) {
+                Ok(()) => return,
+                Err(e) => eprintln!("X11 clipboard notifications unavailable: {}", e),
+            }
+        }
+
+        eprintln!("Falling back to clipboard polling");
+        while on_change.send(Selection::Clipboard).is_ok() && on_change.send(Selection::Primary).is_ok() {
+            thread::sleep(poll_interval());
+        }
+    });
+}
+
+fn err(e: impl std::fmt::Display) -> String {
+    e.to_string()
+}
+
+mod x11 {
+    use super::err;
+    use crate::models::Selection;
+    use crossbeam_channel::Sender;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+    use x11rb::protocol::xproto::ConnectionExt as _;
+    use x11rb::protocol::Event;
+
+    /// Returns Ok when the receiver went away, Err if XFixes can't be used or the connection broke
+    pub fn watch(on_change: &Sender<Selection>) -> Result<(), String> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(err)?;
+        let root = conn.setup().roots[screen_num].root;
+
+        conn.xfixes_query_version(5, 0).map_err(err)?.reply().map_err(err)?;
+        let clipboard = conn.intern_atom(false, b"CLIPBOARD").map_err(err)?.reply().map_err(err)?.atom;
+        let primary = conn.intern_atom(false, b"PRIMARY").map_err(err)?.reply().map_err(err)?.atom;
+
+        let mask = SelectionEventMask::SET_SELECTION_OWNER
+            | SelectionEventMask::SELECTION_WINDOW_DESTROY
+            | SelectionEventMask::SELECTION_CLIENT_CLOSE;
+        conn.xfixes_select_selection_input(root, clipboard, mask).map_err(err)?;
+        conn.xfixes_select_selection_input(root, primary, mask).map_err(err)?;
+        conn.flush().map_err(err)?;
+
+        if on_change.send(Selection::Clipboard).is_err() || on_change.send(Selection::Primary).is_err() {
+            return Ok(());
+        }
+
+        loop {
+            if let Event::XfixesSelectionNotify(ev) = conn.wait_for_event().map_err(err)? {
+                let selection = if ev.selection == primary { Selection::Primary } else { Selection::Clipboard };
+                if on_change.send(selection).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+mod wayland {
+    use super::err;
+    use crate::models::Selection;
+    use crossbeam_channel::Sender;
+    use wayland_client::globals::{registry_queue_init, GlobalListContents};
+    use wayland_client::protocol::wl_registry::WlRegistry;
+    use wayland_client::protocol::wl_seat::WlSeat;
+    use wayland_client::{event_created_child, Connection, Dispatch, QueueHandle};
+    use wayland_protocols_wlr::data_control::v1::client::zwlr_data_control_device_v1::{
+        self, ZwlrDataControlDeviceV1,
+    };
+    use wayland_protocols_wlr::data_control::v1::client::zwlr_data_control_manager_v1::ZwlrDataControlManagerV1;
+    use wayland_protocols_wlr::data_control::v1::client::zwlr_data_control_offer_v1::ZwlrDataControlOfferV1;
+
+    struct State {
+        on_change: Sender<Selection>,
+        offer: Option<ZwlrDataControlOfferV1>,
+        primary_offer: Option<ZwlrDataControlOfferV1>,
+        finished: bool,
+        disconnected: bool,
+    }
+
+    /// Returns Ok when the receiver went away, Err if data-control isn't offered or the connection broke
+    pub fn watch(on_change: &Sender<Selection>) -> Result<(), String> {
+        let conn = Connection::connect_to_env().map_err(err)?;
+        let (globals, mut queue) = registry_queue_init::<State>(&conn).map_err(err)?;
+        let qh = queue.handle();
+
+        let seat: WlSeat = globals.bind(&qh, 1..=2, ()).map_err(err)?;
+        let manager: ZwlrDataControlManagerV1 = globals.bind(&qh, 1..=2, ()).map_err(err)?;
+        let _device = manager.get_data_device(&seat, &qh, ());
+
+        let mut state = State {
+            on_change: on_change.clone(),
+            offer: None,
+            primary_offer: None,
+            finished: false,
+            disconnected: false,
+        };
+
+        // The compositor sends the current selection right after the device is created
+        loop {
+            queue.blocking_dispatch(&mut state).map_err(err)?;
+            if state.disconnected {
+                return Ok(());
+            }
+            if state.finished {
+                return Err("data-control device was invalidated".to_string());
+            }
+        }
+    }
+
+    impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+        fn event(
+            state: &mut Self,
+            _: &ZwlrDataControlDeviceV1,
+            event: zwlr_data_control_device_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            match event {
+                zwlr_data_control_device_v1::Event::Selection { id } => {
+                    if let Some(old) = std::mem::replace(&mut state.offer, id) {
+                        old.destroy();
+                    }
+                    if state.on_change.send(Selection::Clipboard).is_err() {
+                        state.disconnected = true;
+                    }
+                }
+                // Only sent by data-control v2 and newer
+                zwlr_data_control_device_v1::Event::PrimarySelection { id } => {
+                    if let Some(old) = std::mem::replace(&mut state.primary_offer, id) {
+                        old.destroy();
+                    }
+                    if state.on_change.send(Selection::Primary).is_err() {
+                        state.disconnected = true;
+                    }
+                }
+                zwlr_data_control_device_v1::Event::Finished => {
+                    state.finished = true;
+                }
+                _ => {}
+            }
+        }
+
+        event_created_child!(State, ZwlrDataControlDeviceV1, [
+            zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+        ]);
+    }
+
+    impl Dispatch<ZwlrDataControlOfferV1, ()> for State {
+        fn event(
+            _: &mut Self,
+            _: &ZwlrDataControlOfferV1,
+            _: <ZwlrDataControlOfferV1 as wayland_client::Proxy>::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<WlRegistry, GlobalListContents> for State {
+        fn event(
+            _: &mut Self,
+            _: &WlRegistry,
+            _: <WlRegistry as wayland_client::Proxy>::Event,
+            _: &GlobalListContents,
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<WlSeat, ()> for State {
+        fn event(
+            _: &mut Self,
+            _: &WlSeat,
+            _: <WlSeat as wayland_client::Proxy>::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<ZwlrDataControlManagerV1, ()> for State {
+        fn event(
+            _: &mut Self,
+            _: &ZwlrDataControlManagerV1,
+            _: <ZwlrDataControlManagerV1 as wayland_client::Proxy>::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+}
@@ -0,0 +1,33 @@
+// One X connection for the short requests made while capturing and pasting
+// (selection owner lookups, the focused window, XTest). Opened on first use
+// instead of once per request. The change notifications in `watcher` block
+// on their own connection.
+
+use parking_lot::Mutex;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::Window;
+use x11rb::rust_connection::RustConnection;
+
+struct Display {
+    conn: RustConnection,
+    root: Window,
+}
+
+static DISPLAY: Mutex<Option<Display>> = parking_lot::const_mutex(None);
+
+/// Run `request` on the shared connection and the default screen's root window
+pub fn with_connection<T>(request: impl FnOnce(&RustConnection, Window) -> Result<T, String>) -> Result<T, String> {
+    let mut display = DISPLAY.lock();
+    if display.is_none() {
+        let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let root = conn.setup().roots[screen_num].root;
+        *display = Some(Display { conn, root });
+    }
+    let Display { conn, root } = display.as_ref().expect("connected above");
+    let result = request(conn, *root);
+    // The connection may be what failed, start over on the next request
+    if result.is_err() {
+        *display = None;
+    }
+    result
+}